@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use bson::doc;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Utc};
+use futures::FutureExt;
+use mongodb::bson::oid::ObjectId;
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
+
+use crate::collections::{check, Collection};
+use crate::db::{self, Db, DbError};
+use crate::forget::Hook;
+
+pub const COLLECTION: &str = "reaction_stats";
+
+// the rule whose victims are counted as "feur-ed"
+pub const FEUR_RULE: &str = "quoi";
+
+// one automatic reaction that fired
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Firing {
+    _id: ObjectId,
+    pub rule: String,
+    pub guild_id: Option<String>,
+    pub channel_id: String,
+    pub user_id: String,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub at: DateTime<Utc>,
+}
+
+impl Firing {
+    pub fn builder(
+        rule: String,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        user_id: UserId,
+        at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            rule,
+            guild_id: guild_id.map(|id| id.to_string()),
+            channel_id: channel_id.to_string(),
+            user_id: user_id.to_string(),
+            at,
+        }
+    }
+}
+
+pub async fn record(
+    db: &Db,
+    rule: &str,
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    user_id: UserId,
+) -> Result<(), DbError> {
+    // every firing is new, no need to look for an identical one first
+    let firing = Firing::builder(rule.to_owned(), guild_id, channel_id, user_id, Utc::now());
+    let _ = db::insert_raw(db, COLLECTION, bson::to_document(&firing)?).await?;
+    Ok(())
+}
+
+pub async fn guild_firings(
+    db: &Db,
+    guild_id: GuildId,
+    since: DateTime<Utc>,
+) -> Result<Vec<Firing>, DbError> {
+    let filter = doc! {
+        "guild_id": guild_id.to_string(),
+        "at": {"$gte": bson::DateTime::from_chrono(since)},
+    };
+    db::get_objects(db, COLLECTION, filter).await
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Summary {
+    // most fired first
+    pub rules: Vec<(String, usize)>,
+    pub feured: Vec<(String, usize)>,
+    // oldest week first, weeks start on monday
+    pub weeks: Vec<(NaiveDate, usize)>,
+}
+
+pub fn week_start(at: DateTime<Utc>) -> NaiveDate {
+    let date = at.date_naive();
+    date - Duration::days(i64::from(date.weekday().num_days_from_monday()))
+}
+
+// start of the oldest of the last `weeks` weeks, so that it is counted in full
+pub fn since(weeks: u32, now: DateTime<Utc>) -> DateTime<Utc> {
+    let oldest = week_start(now) - Duration::weeks(i64::from(weeks.saturating_sub(1)));
+    oldest.and_time(NaiveTime::MIN).and_utc()
+}
+
+fn ranking<'a>(keys: impl Iterator<Item = &'a str>) -> Vec<(String, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for key in keys {
+        *counts.entry(key).or_default() += 1;
+    }
+    let mut ranking: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(key, count)| (key.to_owned(), count))
+        .collect();
+    ranking.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    ranking
+}
+
+// firings per rule, feur-ed users, and per week over the last `weeks` weeks
+pub fn summarize(firings: &[Firing], weeks: u32, now: DateTime<Utc>) -> Summary {
+    let current = week_start(now);
+    let mut per_week: Vec<(NaiveDate, usize)> = (0..weeks)
+        .rev()
+        .map(|i| (current - Duration::weeks(i64::from(i)), 0))
+        .collect();
+    for firing in firings {
+        let start = week_start(firing.at);
+        if let Some((_, count)) = per_week.iter_mut().find(|(week, _)| *week == start) {
+            *count += 1;
+        }
+    }
+
+    Summary {
+        rules: ranking(firings.iter().map(|f| f.rule.as_str())),
+        feured: ranking(
+            firings
+                .iter()
+                .filter(|f| f.rule == FEUR_RULE)
+                .map(|f| f.user_id.as_str()),
+        ),
+        weeks: per_week,
+    }
+}
+
+pub const COLLECTIONS: &[Collection] = &[Collection {
+    name: COLLECTION,
+    model: check::<Firing>,
+    unique: &[],
+    ttl: None,
+    forget: Some(Hook {
+        label: "déclenchements de réactions",
+        forget: |db, user_id| {
+            db::delete_multiple_query(db, COLLECTION, doc! {"user_id": user_id.to_string()}).boxed()
+        },
+    }),
+}];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_summarize() {
+        // a wednesday
+        let now = Utc.with_ymd_and_hms(2024, 5, 15, 12, 0, 0).unwrap();
+        let firing = |rule: &str, user: u64, days_ago: i64| {
+            Firing::builder(
+                rule.to_owned(),
+                Some(GuildId::new(1)),
+                ChannelId::new(2),
+                UserId::new(user),
+                now - Duration::days(days_ago),
+            )
+        };
+        let firings = [
+            firing("quoi", 10, 0),
+            firing("quoi", 10, 1),
+            firing("quoi", 11, 3),
+            firing("sus", 11, 7),
+            firing("sus", 12, 30),
+        ];
+
+        let summary = summarize(&firings, 2, now);
+        assert_eq!(
+            summary.rules,
+            vec![("quoi".to_owned(), 3), ("sus".to_owned(), 2)]
+        );
+        assert_eq!(
+            summary.feured,
+            vec![("10".to_owned(), 2), ("11".to_owned(), 1)]
+        );
+        let monday = NaiveDate::from_ymd_opt(2024, 5, 13).unwrap();
+        assert_eq!(week_start(now), monday);
+        assert_eq!(
+            since(2, now),
+            Utc.with_ymd_and_hms(2024, 5, 6, 0, 0, 0).unwrap()
+        );
+        assert_eq!(
+            summary.weeks,
+            vec![(monday - Duration::weeks(1), 2), (monday, 2)]
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::{HashSet, VecDeque};
+
+use serenity::model::prelude::MessageId;
+use serenity::prelude::Context;
+use tracing::error;
+
+use crate::AnsweredContainer;
+
+// edits rarely come long after the message, older ids are forgotten
+const CAPACITY: usize = 1000;
+
+// messages the bot already replied to, so an edit doesn't get a second reply
+#[derive(Debug, Default)]
+pub struct Answered {
+    order: VecDeque<MessageId>,
+    ids: HashSet<MessageId>,
+}
+
+impl Answered {
+    pub fn contains(&self, id: MessageId) -> bool {
+        self.ids.contains(&id)
+    }
+
+    pub fn insert(&mut self, id: MessageId) {
+        if !self.ids.insert(id) {
+            return;
+        }
+        self.order.push_back(id);
+        if self.order.len() > CAPACITY {
+            if let Some(oldest) = self.order.pop_front() {
+                self.ids.remove(&oldest);
+            }
+        }
+    }
+}
+
+pub async fn is_answered(ctx: &Context, id: MessageId) -> bool {
+    let data = ctx.data.read().await;
+    let Some(answered) = data.get::<AnsweredContainer>() else {
+        return false;
+    };
+    let answered = answered.lock().await;
+    answered.contains(id)
+}
+
+pub async fn mark(ctx: &Context, id: MessageId) {
+    let data = ctx.data.read().await;
+    if let Some(answered) = data.get::<AnsweredContainer>() {
+        answered.lock().await.insert(id);
+    } else {
+        error!("there was a problem getting the answered messages");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_answered() {
+        let mut answered = Answered::default();
+        answered.insert(MessageId::new(1));
+        answered.insert(MessageId::new(1));
+        assert!(answered.contains(MessageId::new(1)));
+        assert_eq!(answered.order.len(), 1);
+
+        for id in 2..=CAPACITY as u64 + 1 {
+            answered.insert(MessageId::new(id));
+        }
+        assert!(!answered.contains(MessageId::new(1)));
+        assert!(answered.contains(MessageId::new(2)));
+        assert_eq!(answered.ids.len(), CAPACITY);
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::{BTreeMap, HashSet};
+
+use anyhow::{anyhow, bail};
+use bson::{doc, Bson, Document};
+use tracing::error;
+
+use crate::collections;
+use crate::db::{self, Db, DbError};
+use crate::migrations::{self, Report, MIGRATIONS};
+use crate::storage::matcher::UniqueIndexes;
+
+// bumped when the layout of the archive itself changes, not the documents
+pub const FORMAT: i32 = 1;
+
+// a failed restore, the data from before it is still there unless the rollback failed too
+#[derive(Debug)]
+pub enum RestoreError {
+    Kept(DbError),
+    Lost { error: DbError, previous: Archive },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Archive {
+    pub created_at: bson::DateTime,
+    pub collections: BTreeMap<String, Vec<Document>>,
+}
+
+impl Archive {
+    pub async fn export(db: &Db) -> Result<Self, DbError> {
+        let mut collections = BTreeMap::new();
+        for collection in collections::all() {
+            let documents = db::raw_documents(db, collection.name, doc! {}).await?;
+            collections.insert(collection.name.to_owned(), documents);
+        }
+        Ok(Self {
+            created_at: bson::DateTime::now(),
+            collections,
+        })
+    }
+
+    pub fn documents(&self) -> usize {
+        self.collections.values().map(Vec::len).sum()
+    }
+
+    // canonical extended json, so that ids, dates and number types come back unchanged
+    pub fn to_json(&self) -> Result<Vec<u8>, DbError> {
+        let collections: Document = self
+            .collections
+            .iter()
+            .map(|(name, documents)| (name.clone(), Bson::from(documents.clone())))
+            .collect();
+        let archive = doc! {
+            "format": FORMAT,
+            "created_at": self.created_at,
+            "collections": collections,
+        };
+        serde_json::to_vec_pretty(&Bson::Document(archive).into_canonical_extjson())
+            .map_err(|e| DbError::Serialization(e.to_string()))
+    }
+
+    // checks everything that could make the restore fail halfway
+    pub fn from_json(bytes: &[u8]) -> Result<Self, anyhow::Error> {
+        let value: serde_json::Value =
+            serde_json::from_slice(bytes).map_err(|e| anyhow!("JSON invalide : {e}"))?;
+        let Bson::Document(archive) =
+            Bson::try_from(value).map_err(|e| anyhow!("JSON étendu invalide : {e}"))?
+        else {
+            bail!("l'archive n'est pas un objet");
+        };
+
+        match archive.get_i32("format") {
+            Ok(FORMAT) => {}
+            Ok(format) => bail!("format d'archive {format} non supporté"),
+            Err(_) => bail!("ce fichier n'est pas une sauvegarde du bot"),
+        }
+        let created_at = *archive
+            .get_datetime("created_at")
+            .map_err(|_| anyhow!("date de la sauvegarde manquante"))?;
+        let stored = archive
+            .get_document("collections")
+            .map_err(|_| anyhow!("collections manquantes"))?;
+
+        let mut unique_indexes = UniqueIndexes::default();
+        for collection in collections::all() {
+            for fields in collection.unique {
+                unique_indexes.add(collection.name, fields);
+            }
+        }
+        let mut collections = BTreeMap::new();
+        for (name, documents) in stored {
+            if collections::get(name).is_none() {
+                bail!("collection inconnue : {name}");
+            }
+            let Bson::Array(documents) = documents else {
+                bail!("{name} n'est pas une liste");
+            };
+            let documents = documents
+                .iter()
+                .map(|d| d.as_document().cloned())
+                .collect::<Option<Vec<Document>>>()
+                .ok_or_else(|| anyhow!("{name} contient autre chose que des documents"))?;
+            check_documents(name, &documents, &unique_indexes)?;
+            collections.insert(name.clone(), documents);
+        }
+
+        let archive = Self {
+            created_at,
+            collections,
+        };
+        let latest = MIGRATIONS.last().map_or(0, |m| m.version);
+        if archive.schema_version() > latest {
+            bail!(
+                "sauvegarde faite par une version plus récente du bot (schéma {} > {latest})",
+                archive.schema_version()
+            );
+        }
+        archive.check_models()?;
+        Ok(archive)
+    }
+
+    // the documents must be readable by the bot once the missing migrations ran,
+    // they are applied to a copy like migrations::run will do after the restore
+    fn check_models(&self) -> Result<(), anyhow::Error> {
+        let mut collections = self.collections.clone();
+        let current = self.schema_version();
+        for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+            if let Some(documents) = collections.get_mut(migration.collection) {
+                for document in documents {
+                    (migration.apply)(document);
+                }
+            }
+        }
+        for (name, documents) in collections {
+            let Some(collection) = collections::get(&name) else {
+                bail!("collection inconnue : {name}");
+            };
+            for document in documents {
+                let id = document.get("_id").cloned().unwrap_or(Bson::Null);
+                (collection.model)(document)
+                    .map_err(|e| anyhow!("document {id} illisible dans {name} : {e}"))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn schema_version(&self) -> u32 {
+        self.collections
+            .get(migrations::COLLECTION)
+            .and_then(|documents| documents.first())
+            .and_then(|d| d.get_i64("version").ok())
+            .and_then(|v| u32::try_from(v).ok())
+            .unwrap_or(0)
+    }
+
+    // the collections of the archive replace the stored ones, then the missing migrations run,
+    // if anything fails the data from before the restore is written back
+    pub async fn restore(&self, db: &Db) -> Result<Vec<Report>, RestoreError> {
+        let previous = Self::export(db).await.map_err(RestoreError::Kept)?;
+        let error = match self.write(db).await {
+            Ok(()) => match migrations::run(db, false).await {
+                Ok(reports) => return Ok(reports),
+                Err(e) => e,
+            },
+            Err(e) => e,
+        };
+        error!(
+            "error while restoring the backup of {}, rolling back : {error}",
+            self.created_at
+        );
+        match previous.write(db).await {
+            Ok(()) => Err(RestoreError::Kept(error)),
+            Err(rollback) => {
+                error!("error while rolling back the restore : {rollback}");
+                Err(RestoreError::Lost { error, previous })
+            }
+        }
+    }
+
+    // the schema version is always replaced, without it every migration runs again
+    // like the ones check_models applied to the documents
+    async fn write(&self, db: &Db) -> Result<(), DbError> {
+        if !self.collections.contains_key(migrations::COLLECTION) {
+            db::delete_multiple_query(db, migrations::COLLECTION, doc! {}).await?;
+        }
+        for (collection, documents) in &self.collections {
+            db::delete_multiple_query(db, collection, doc! {}).await?;
+            for document in documents {
+                db::insert_raw(db, collection, document.clone()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_documents(
+    collection: &str,
+    documents: &[Document],
+    unique_indexes: &UniqueIndexes,
+) -> Result<(), anyhow::Error> {
+    let mut ids = HashSet::new();
+    for document in documents {
+        let Some(id) = document.get("_id") else {
+            bail!("document sans _id dans {collection}");
+        };
+        if !ids.insert(id.to_string()) {
+            bail!("_id {id} en double dans {collection}");
+        }
+        if unique_indexes
+            .check(collection, document, documents.iter())
+            .is_err()
+        {
+            bail!("document en double dans {collection} : {document}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::commands::macros::model::{Macro, Macros, TempMacros};
+    use crate::db::User;
+    use crate::repository::{MuteUsers, Repository};
+
+    #[tokio::test]
+    async fn test_backup() {
+        let db = Db::memory();
+        MuteUsers::insert(&db, &User::builder("10".to_owned()))
+            .await
+            .unwrap();
+        let init = Macro::builder("10".to_owned(), "init".to_owned(), "roll".to_owned(), None);
+        Macros::insert(&db, &init).await.unwrap();
+        migrations::run(&db, false).await.unwrap();
+
+        let archive = Archive::export(&db).await.unwrap();
+        assert_eq!(archive.documents(), 3);
+        let json = archive.to_json().unwrap();
+        assert_eq!(Archive::from_json(&json).unwrap(), archive);
+
+        let restored = Db::memory();
+        MuteUsers::insert(&restored, &User::builder("11".to_owned()))
+            .await
+            .unwrap();
+        assert!(archive.restore(&restored).await.unwrap().is_empty());
+        let users = MuteUsers::all(&restored).await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].user_id, "10");
+        assert_eq!(Macros::all(&restored).await.unwrap().len(), 1);
+
+        // an archive without schema version is migrated from the start
+        let temp_macro =
+            doc! {"_id": bson::oid::ObjectId::new(), "user_id": "10", "command": "roll"};
+        let old = Archive {
+            created_at: bson::DateTime::now(),
+            collections: BTreeMap::from([(TempMacros::COLLECTION.to_owned(), vec![temp_macro])]),
+        };
+        assert_eq!(
+            old.restore(&restored).await.unwrap().len(),
+            MIGRATIONS.len()
+        );
+        assert_eq!(TempMacros::all(&restored).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rollback() {
+        let db = Db::memory();
+        MuteUsers::insert(&db, &User::builder("10".to_owned()))
+            .await
+            .unwrap();
+        // an index of the live db that the archive doesn't respect
+        db::create_unique_index(&db, MuteUsers::COLLECTION, &["note"])
+            .await
+            .unwrap();
+        let users = [11, 12].map(|id| bson::to_document(&User::builder(id.to_string())).unwrap());
+        let archive = Archive {
+            created_at: bson::DateTime::now(),
+            collections: BTreeMap::from([(MuteUsers::COLLECTION.to_owned(), users.to_vec())]),
+        };
+
+        assert!(matches!(
+            archive.restore(&db).await,
+            Err(RestoreError::Kept(DbError::AlreadyExists))
+        ));
+        let users = MuteUsers::all(&db).await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].user_id, "10");
+    }
+
+    #[test]
+    fn test_invalid_archives() {
+        let archive = |collections: Document| {
+            let archive = doc! {
+                "format": FORMAT,
+                "created_at": bson::DateTime::now(),
+                "collections": collections,
+            };
+            serde_json::to_vec(&Bson::Document(archive).into_canonical_extjson()).unwrap()
+        };
+        let user = doc! {"_id": bson::oid::ObjectId::new(), "user_id": "10"};
+
+        assert!(Archive::from_json(b"{}").is_err());
+        assert!(Archive::from_json(b"pas du json").is_err());
+        assert!(Archive::from_json(&archive(doc! {"mute_users": [user.clone()]})).is_ok());
+        assert!(Archive::from_json(&archive(doc! {"inconnue": []})).is_err());
+        assert!(Archive::from_json(&archive(doc! {"mute_users": [{"user_id": "10"}]})).is_err());
+        let mut copy = user.clone();
+        copy.insert("_id", bson::oid::ObjectId::new());
+        assert!(Archive::from_json(&archive(doc! {"mute_users": [user, copy]})).is_err());
+        assert!(Archive::from_json(&archive(
+            doc! {"schema_version": [{"_id": 1, "version": 99_i64}]}
+        ))
+        .is_err());
+        // a document that the bot couldn't read
+        let user = doc! {"_id": bson::oid::ObjectId::new(), "user_id": 10};
+        assert!(Archive::from_json(&archive(doc! {"mute_users": [user]})).is_err());
+        // the temporary macros of an old archive get their date from a migration
+        let temp_macro =
+            doc! {"_id": bson::oid::ObjectId::new(), "user_id": "10", "command": "roll"};
+        assert!(Archive::from_json(&archive(doc! {"temp_macros": [temp_macro.clone()]})).is_ok());
+        assert!(Archive::from_json(&archive(doc! {
+            "temp_macros": [temp_macro],
+            "schema_version": [{"_id": 1, "version": 3_i64}],
+        }))
+        .is_err());
+    }
+}
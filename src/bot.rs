@@ -9,8 +9,9 @@ use serenity::{async_trait, prelude::*};
 use tracing::{error, info};
 
 use crate::commands::{general::roll, PoiseError};
+use crate::db::{Db, DbError};
 use crate::message::handle_reaction;
-use crate::{loops, Data, GuildIdContainer};
+use crate::{answered, emojis, forget, game_nights, loops, polls, Data, GuildIdContainer};
 
 pub struct Bot {
     pub is_loop_running: AtomicBool,
@@ -22,7 +23,8 @@ impl EventHandler for Bot {
         info!("{} is connected!", ready.user.name);
 
         let ctx_arc: Arc<Context> = Arc::new(ctx);
-        if !self.is_loop_running.load(Ordering::Relaxed) {
+        // loops are spawned once, ready is sent again on reconnections
+        if !self.is_loop_running.swap(true, Ordering::Relaxed) {
             let ctx1 = Arc::clone(&ctx_arc);
 
             tokio::spawn(async move {
@@ -89,6 +91,34 @@ pub async fn register_guild(
     }
 }
 
+// started from the framework setup, the only place with both the context and the database
+pub fn spawn_scheduler(ctx: &serenity_prelude::Context, db: Db) {
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        loop {
+            loops::scheduler(&ctx, &db).await;
+            tokio::time::sleep(Duration::from_secs(loops::SCHEDULER_PERIOD)).await;
+        }
+    });
+}
+
+pub async fn load_emojis(ctx: &serenity_prelude::Context) {
+    let guilds = {
+        let data = ctx.data.read().await;
+        let Some(guild_group) = data.get::<GuildIdContainer>() else {
+            error!("There was a problem getting the guild id");
+            return;
+        };
+        guild_group.0.clone()
+    };
+
+    for guild in guilds {
+        if let Err(e) = emojis::fetch_guild(ctx, guild).await {
+            error!("Guild {guild} had an error getting emojis : {e}");
+        }
+    }
+}
+
 pub fn apply_desc_from(commands: &mut [poise::Command<Data, PoiseError>], locale: &str) {
     for command in commands {
         if let Some(desc) = command.description_localizations.get(locale) {
@@ -96,6 +126,31 @@ pub fn apply_desc_from(commands: &mut [poise::Command<Data, PoiseError>], locale
                 command.description = Some(desc.to_string());
             }
         }
+        apply_desc_from(&mut command.subcommands, locale);
+    }
+}
+
+// database errors bubbling up from a command get a readable reply,
+// the others keep poise's default which shows their text
+pub async fn on_error(error: poise::FrameworkError<'_, Data, PoiseError>) {
+    match error {
+        poise::FrameworkError::Command { ref error, ctx, .. } if error.is::<DbError>() => {
+            error!(
+                "error in command {} : {error}",
+                ctx.command().qualified_name
+            );
+            let content = error
+                .downcast_ref::<DbError>()
+                .map_or_else(|| error.to_string(), |e| e.reply().to_owned());
+            if let Err(e) = ctx.say(content).await {
+                error!("error while replying to a failed command : {e}");
+            }
+        }
+        error => {
+            if let Err(e) = poise::builtins::on_error(error).await {
+                error!("error while handling an error : {e}");
+            }
+        }
     }
 }
 
@@ -103,38 +158,111 @@ pub async fn event_handler(
     ctx: &serenity_prelude::Context,
     event: &serenity_prelude::FullEvent,
     _: poise::FrameworkContext<'_, Data, PoiseError>,
-    _: &Data,
+    data: &Data,
 ) -> Result<(), PoiseError> {
     match event {
         serenity_prelude::FullEvent::Ready { data_about_bot, .. } => {
             info!("Logged in as {}", data_about_bot.user.name);
         }
+        serenity_prelude::FullEvent::GuildEmojisUpdate {
+            guild_id,
+            current_state,
+        } => {
+            emojis::set_guild(ctx, *guild_id, current_state.values().cloned().collect()).await;
+        }
+        serenity_prelude::FullEvent::InteractionCreate {
+            interaction: serenity_prelude::Interaction::Component(component),
+        } => {
+            handle_component(ctx, &data.db, component).await?;
+        }
         serenity_prelude::FullEvent::Message { new_message } => {
-            if msg_check(new_message) {
-                let res = match handle_reaction(ctx, new_message).await {
-                    Ok(s) => s,
-                    Err(e) => {
-                        // not important, just log and return
-                        error!("message checked err: {e}");
-                        return Ok(());
-                    }
-                };
-                if let Some(content) = res {
-                    let _ = new_message.channel_id.say(&ctx.http, content).await?;
-                }
-            } else if new_message.content.starts_with("$roll") {
-                let rest = new_message.content[5..].to_string();
-                if let Err(e) = roll::roll_intern_str(ctx, &new_message.channel_id, rest).await {
-                    error!("message $roll err: {e}");
-                    return Err(e);
-                }
+            handle_message(ctx, &data.db, new_message).await?;
+        }
+        serenity_prelude::FullEvent::MessageUpdate {
+            old_if_available,
+            new,
+            event,
+        } => {
+            // embeds being resolved also send updates, without content
+            let Some(content) = &event.content else {
+                return Ok(());
+            };
+            // the bot edits its own polls and game nights on every vote,
+            // bots are ignored before fetching the message
+            if event.author.as_ref().is_some_and(|author| author.bot) {
+                return Ok(());
+            }
+            if old_if_available
+                .as_ref()
+                .is_some_and(|old| &old.content == content)
+            {
+                return Ok(());
             }
+            let message = match new {
+                Some(message) => message.clone(),
+                None => event.channel_id.message(&ctx.http, event.id).await?,
+            };
+            handle_message(ctx, &data.db, &message).await?;
         }
         _ => {}
     }
     Ok(())
 }
 
+// buttons are routed by the prefix of their custom id
+async fn handle_component(
+    ctx: &serenity_prelude::Context,
+    db: &Db,
+    component: &serenity_prelude::ComponentInteraction,
+) -> Result<(), PoiseError> {
+    let prefix = component
+        .data
+        .custom_id
+        .split(':')
+        .next()
+        .unwrap_or_default();
+    match prefix {
+        polls::BUTTON_PREFIX => polls::vote(ctx, db, component).await?,
+        game_nights::BUTTON_PREFIX => game_nights::answer(ctx, db, component).await?,
+        forget::BUTTON_PREFIX => forget::confirm(ctx, db, component).await?,
+        _ => {}
+    }
+    Ok(())
+}
+
+// shared by new and edited messages, a message gets at most one reply
+async fn handle_message(
+    ctx: &serenity_prelude::Context,
+    db: &Db,
+    message: &serenity_prelude::Message,
+) -> Result<(), PoiseError> {
+    if answered::is_answered(ctx, message.id).await {
+        return Ok(());
+    }
+    if msg_check(message) {
+        let res = match handle_reaction(ctx, db, message).await {
+            Ok(s) => s,
+            Err(e) => {
+                // not important, just log and return
+                error!("message checked err: {e}");
+                return Ok(());
+            }
+        };
+        if let Some(content) = res {
+            answered::mark(ctx, message.id).await;
+            let _ = message.channel_id.say(&ctx.http, content).await?;
+        }
+    } else if message.content.starts_with("$roll") {
+        let rest = message.content[5..].to_string();
+        answered::mark(ctx, message.id).await;
+        if let Err(e) = roll::roll_intern_str(ctx, &message.channel_id, rest).await {
+            error!("message $roll err: {e}");
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
 fn msg_check(msg: &serenity_prelude::Message) -> bool {
     !msg.content.is_empty() && !msg.content.starts_with('$') && !msg.author.bot
 }
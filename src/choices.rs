@@ -0,0 +1,119 @@
+use anyhow::anyhow;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Choice {
+    pub label: String,
+    pub weight: u32,
+}
+
+// options separated by " ou ", "|" or ",", weighted ones can have a weight after
+// a label that isn't a number: "pizza:3", but "18:30" stays an option
+pub fn parse_options(s: &str, weighted: bool) -> Result<Vec<Choice>, anyhow::Error> {
+    let choices = s
+        .replace(" ou ", "|")
+        .split(['|', ','])
+        .map(str::trim)
+        .filter(|option| !option.is_empty())
+        .map(|option| parse_choice(option, weighted))
+        .collect::<Result<Vec<Choice>, anyhow::Error>>()?;
+    if choices.len() < 2 {
+        return Err(anyhow!("il faut au moins deux options"));
+    }
+    Ok(choices)
+}
+
+fn parse_choice(option: &str, weighted: bool) -> Result<Choice, anyhow::Error> {
+    let (label, weight) = match option.rsplit_once(':') {
+        Some((label, weight))
+            if weighted
+                && label.trim().parse::<f64>().is_err()
+                && weight.trim().parse::<u32>().is_ok() =>
+        {
+            (label.trim(), weight.trim().parse::<u32>()?)
+        }
+        _ => (option, 1),
+    };
+    if weight == 0 {
+        return Err(anyhow!("le poids de \"{label}\" doit être positif"));
+    }
+    Ok(Choice {
+        label: label.to_owned(),
+        weight,
+    })
+}
+
+// weighted draw of `count` options, each option drawn at most once
+pub fn draw<'a, R: Rng>(rng: &mut R, choices: &'a [Choice], count: usize) -> Vec<&'a Choice> {
+    let mut remaining: Vec<&Choice> = choices.iter().collect();
+    let mut drawn = Vec::new();
+    while drawn.len() < count {
+        let Ok(choice) = remaining.choose_weighted(rng, |c| c.weight).copied() else {
+            break;
+        };
+        remaining.retain(|c| !std::ptr::eq(*c, choice));
+        drawn.push(choice);
+    }
+    drawn
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn choice(label: &str, weight: u32) -> Choice {
+        Choice {
+            label: label.to_owned(),
+            weight,
+        }
+    }
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!(
+            parse_options("pizza ou sushi|kebab, tacos", true).unwrap(),
+            vec![
+                choice("pizza", 1),
+                choice("sushi", 1),
+                choice("kebab", 1),
+                choice("tacos", 1)
+            ]
+        );
+        assert_eq!(
+            parse_options("pizza:3 ou sushi : 2 ou 12:30", true).unwrap(),
+            vec![choice("pizza", 3), choice("sushi", 2), choice("12:30", 1)]
+        );
+        assert_eq!(
+            parse_options("a:b ou c", true).unwrap(),
+            vec![choice("a:b", 1), choice("c", 1)]
+        );
+        // only /choose has weights
+        assert_eq!(
+            parse_options("pizza:3 ou 18:00", false).unwrap(),
+            vec![choice("pizza:3", 1), choice("18:00", 1)]
+        );
+        assert!(parse_options("pizza", true).is_err());
+        assert!(parse_options("pizza:0 ou sushi", true).is_err());
+    }
+
+    #[test]
+    fn test_draw() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let choices = [choice("a", 1), choice("b", 1), choice("c", 1)];
+
+        let drawn = draw(&mut rng, &choices, 3);
+        assert_eq!(drawn.len(), 3);
+        for c in &choices {
+            assert!(drawn.contains(&c));
+        }
+        assert_eq!(draw(&mut rng, &choices, 10).len(), 3);
+
+        let weighted = [choice("a", 1), choice("b", 1_000_000)];
+        let firsts = (0..20)
+            .filter(|_| draw(&mut rng, &weighted, 1)[0].label == "b")
+            .count();
+        assert!(firsts >= 19);
+    }
+}
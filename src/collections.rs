@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use bson::Document;
+use serde::de::DeserializeOwned;
+
+use crate::commands::{macros, pdx};
+use crate::forget::Hook;
+use crate::{
+    analytics, cooldown, game_nights, migrations, mute, polls, quotes, ratings, reactions,
+    reminders,
+};
+
+// a collection of the bot, declared by the module owning it:
+// backups, indexes and /oublie-moi all go through the list below
+pub struct Collection {
+    pub name: &'static str,
+    // fails on a document that the bot can't read, see check
+    pub model: fn(Document) -> Result<(), bson::de::Error>,
+    // fields identifying a document, duplicates are rejected by the storage
+    pub unique: &'static [&'static [&'static str]],
+    // transient documents, deleted once the date field is older than the delay
+    pub ttl: Option<(&'static str, Duration)>,
+    // what /oublie-moi removes, None when the documents don't identify a user
+    pub forget: Option<Hook>,
+}
+
+// the model of a collection is the type its documents deserialize into
+pub fn check<T: DeserializeOwned>(document: Document) -> Result<(), bson::de::Error> {
+    bson::from_document::<T>(document).map(|_| ())
+}
+
+// the collections of every module storing data
+const MODULES: &[&[Collection]] = &[
+    mute::COLLECTIONS,
+    macros::model::COLLECTIONS,
+    pdx::model::COLLECTIONS,
+    reactions::COLLECTIONS,
+    cooldown::COLLECTIONS,
+    analytics::COLLECTIONS,
+    quotes::COLLECTIONS,
+    reminders::COLLECTIONS,
+    polls::COLLECTIONS,
+    game_nights::COLLECTIONS,
+    ratings::COLLECTIONS,
+    migrations::COLLECTIONS,
+];
+
+pub fn all() -> impl Iterator<Item = &'static Collection> {
+    MODULES.iter().flat_map(|collections| collections.iter())
+}
+
+pub fn get(name: &str) -> Option<&'static Collection> {
+    all().find(|collection| collection.name == name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        let mut names: Vec<&str> = all().map(|c| c.name).collect();
+        let count = names.len();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), count);
+        assert!(get("macros").is_some());
+        assert!(get("inconnue").is_none());
+    }
+}
@@ -0,0 +1,136 @@
+use poise::serenity_prelude::{self as serenity, CreateAttachment};
+use tracing::{error, info};
+
+use crate::backup::{Archive, RestoreError};
+use crate::commands::{Context, PoiseError};
+use crate::{cooldown, mute, reactions};
+
+// bigger archives are written next to the bot instead of being sent on discord
+const ATTACHMENT_LIMIT: usize = 8 * 1024 * 1024;
+
+#[poise::command(
+    slash_command,
+    category = "admin",
+    hide_in_help,
+    owners_only,
+    subcommands("backup", "restore"),
+    subcommand_required,
+    description_localized("fr", "Commandes réservées au propriétaire du bot")
+)]
+pub async fn admin(_ctx: Context<'_>) -> Result<(), PoiseError> {
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    owners_only,
+    ephemeral,
+    description_localized("fr", "Exporte toutes les données du bot")
+)]
+async fn backup(ctx: Context<'_>) -> Result<(), PoiseError> {
+    ctx.defer_ephemeral().await?;
+    let archive = Archive::export(&ctx.data().db).await?;
+    let json = archive.to_json()?;
+    let name = format!(
+        "teamy-{}.json",
+        archive.created_at.to_chrono().format("%Y-%m-%d-%H%M")
+    );
+    let content = format!(
+        "Sauvegarde de {} document(s) dans {} collection(s)",
+        archive.documents(),
+        archive.collections.len()
+    );
+
+    if json.len() > ATTACHMENT_LIMIT {
+        std::fs::write(&name, &json)?;
+        info!("backup written to {name}");
+        ctx.say(format!(
+            "{content}, trop grosse pour discord : écrite dans {name}"
+        ))
+        .await?;
+    } else {
+        ctx.send(
+            poise::CreateReply::default()
+                .content(content)
+                .attachment(CreateAttachment::bytes(json, name)),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    owners_only,
+    ephemeral,
+    description_localized("fr", "Remplace les données du bot par celles d'une sauvegarde")
+)]
+async fn restore(
+    ctx: Context<'_>,
+    #[description = "fichier créé par /admin backup"] fichier: serenity::Attachment,
+) -> Result<(), PoiseError> {
+    ctx.defer_ephemeral().await?;
+    let bytes = fichier.download().await?;
+    let archive = match Archive::from_json(&bytes) {
+        Ok(archive) => archive,
+        Err(e) => {
+            ctx.say(format!("Sauvegarde invalide, rien n'a été modifié : {e}"))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let db = &ctx.data().db;
+    let migrations = match archive.restore(db).await {
+        Ok(migrations) => migrations,
+        Err(RestoreError::Kept(e)) => {
+            ctx.say(format!(
+                "{}, les données d'avant la restauration sont toujours là",
+                e.reply()
+            ))
+            .await?;
+            return Ok(());
+        }
+        // the data from before is only in memory now, it is written next to the bot
+        Err(RestoreError::Lost { error, previous }) => {
+            let name = format!(
+                "teamy-avant-restauration-{}.json",
+                previous.created_at.to_chrono().format("%Y-%m-%d-%H%M")
+            );
+            std::fs::write(&name, previous.to_json()?)?;
+            error!("data from before the failed restore written to {name}");
+            ctx.say(format!(
+                "{}, et les données d'avant la restauration n'ont pas pu être remises : \
+                 elles sont écrites dans {name}, restaurez-les",
+                error.reply()
+            ))
+            .await?;
+            return Ok(());
+        }
+    };
+    info!(
+        "backup of {} restored by {}",
+        archive.created_at,
+        ctx.author().id
+    );
+
+    // the caches still hold the replaced data
+    let serenity_ctx = ctx.serenity_context();
+    if let Err(e) = mute::load(serenity_ctx, db).await {
+        error!("error while reloading muted users, chans and guilds : {e}");
+    }
+    if let Err(e) = cooldown::load(serenity_ctx, db).await {
+        error!("error while reloading reaction cooldowns : {e}");
+    }
+    reactions::invalidate_all(serenity_ctx).await;
+
+    ctx.say(format!(
+        "Sauvegarde du {} restaurée : {} document(s) dans {} collection(s), {} migration(s) appliquée(s)",
+        archive.created_at.to_chrono().format("%d/%m/%Y %H:%M"),
+        archive.documents(),
+        archive.collections.len(),
+        migrations.len()
+    ))
+    .await?;
+    Ok(())
+}
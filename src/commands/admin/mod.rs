@@ -1 +1,3 @@
+pub mod backup;
+pub mod reaction;
 pub mod register;
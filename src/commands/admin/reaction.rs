@@ -0,0 +1,237 @@
+use bson::doc;
+use poise::serenity_prelude::{self as serenity, CreateEmbed};
+
+use crate::commands::{Context, PoiseError};
+use crate::cooldown::Limits;
+use crate::db;
+use crate::message::{self, Action, MessageInfo};
+use crate::reactions::{self, MatchKind, ReactionRule};
+use crate::template;
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "admin",
+    default_member_permissions = "ADMINISTRATOR",
+    subcommands("add", "remove", "list", "test"),
+    subcommand_required,
+    description_localized("fr", "Gère les réactions automatiques du serveur")
+)]
+pub async fn reaction(_ctx: Context<'_>) -> Result<(), PoiseError> {
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("fr", "Ajoute une réaction automatique")
+)]
+#[allow(clippy::too_many_arguments)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "nom de la réaction"] nom: String,
+    #[description = "type de correspondance"] correspondance: MatchKind,
+    #[description = "mots déclencheurs, séparés par des virgules"] mots: String,
+    #[description = "réponses séparées par des |, avec {user}, {channel}, {emoji:nom}, {random:a|b}"]
+    reponses: Option<String>,
+    #[description = "emojis à ajouter au message, séparés par des espaces"] emojis: Option<String>,
+    #[description = "probabilité de déclenchement en pourcents (100 par défaut)"]
+    #[min = 1_u8]
+    #[max = 100_u8]
+    probabilite: Option<u8>,
+    #[description = "délai en secondes avant de pouvoir se redéclencher dans le salon"]
+    cooldown_salon: Option<u64>,
+    #[description = "délai en secondes avant de pouvoir se redéclencher pour un utilisateur"]
+    cooldown_utilisateur: Option<u64>,
+) -> Result<(), PoiseError> {
+    let guild_id = ctx.guild_id().ok_or("pas de serveur")?;
+
+    let words = split_list(&mots.to_lowercase(), ',');
+    let replies: Vec<String> = reponses.map_or_else(Vec::new, |r| {
+        template::split_options(&r)
+            .into_iter()
+            .map(str::trim)
+            .filter(|reply| !reply.is_empty())
+            .map(str::to_owned)
+            .collect()
+    });
+    let emojis: Vec<String> = emojis.map_or_else(Vec::new, |e| {
+        e.split_whitespace().map(str::to_owned).collect()
+    });
+    if words.is_empty() {
+        ctx.say("Il faut au moins un mot déclencheur").await?;
+        return Ok(());
+    }
+    if replies.is_empty() && emojis.is_empty() {
+        ctx.say("Il faut au moins une réponse ou un emoji").await?;
+        return Ok(());
+    }
+    for emoji in &emojis {
+        if serenity::ReactionType::try_from(emoji.as_str()).is_err() {
+            ctx.say(format!("\"{emoji}\" n'est pas un emoji valide"))
+                .await?;
+            return Ok(());
+        }
+    }
+
+    let db = &ctx.data().db;
+    let filter = doc! {"guild_id": guild_id.to_string(), "name": &nom};
+    if db::find_filter::<ReactionRule>(db, reactions::COLLECTION, filter)
+        .await?
+        .is_some()
+    {
+        ctx.say(format!("La réaction \"{nom}\" existe déjà"))
+            .await?;
+        return Ok(());
+    }
+
+    let rule = ReactionRule::builder(
+        guild_id.to_string(),
+        nom.clone(),
+        correspondance,
+        words,
+        replies,
+        emojis,
+        Limits::new(
+            f64::from(probabilite.unwrap_or(100)) / 100.0_f64,
+            cooldown_salon.unwrap_or(0),
+            cooldown_utilisateur.unwrap_or(0),
+        ),
+    );
+    let _ = db::insert(db, reactions::COLLECTION, &rule).await?;
+    reactions::invalidate(ctx.serenity_context(), guild_id).await;
+
+    ctx.say(format!("La réaction \"{nom}\" a bien été ajoutée"))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("fr", "Supprime une réaction automatique")
+)]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "nom de la réaction"] nom: String,
+) -> Result<(), PoiseError> {
+    let guild_id = ctx.guild_id().ok_or("pas de serveur")?;
+    let db = &ctx.data().db;
+
+    let filter = doc! {"guild_id": guild_id.to_string(), "name": &nom};
+    let content = match db::find_filter::<ReactionRule>(db, reactions::COLLECTION, filter).await? {
+        Some(rule) => {
+            db::delete(db, reactions::COLLECTION, &rule).await?;
+            reactions::invalidate(ctx.serenity_context(), guild_id).await;
+            format!("La réaction \"{nom}\" a bien été supprimée")
+        }
+        None => format!("La réaction \"{nom}\" n'existe pas"),
+    };
+    ctx.say(content).await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("fr", "Affiche les réactions automatiques du serveur")
+)]
+async fn list(ctx: Context<'_>) -> Result<(), PoiseError> {
+    let guild_id = ctx.guild_id().ok_or("pas de serveur")?;
+    let rules = reactions::guild_rules(ctx.serenity_context(), &ctx.data().db, guild_id).await?;
+
+    if rules.is_empty() {
+        ctx.say("Aucune réaction personnalisée sur ce serveur")
+            .await?;
+        return Ok(());
+    }
+
+    let fields: Vec<(String, String, bool)> = rules
+        .iter()
+        .map(|rule| (rule.name.clone(), describe(rule), false))
+        .collect();
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::new()
+                .title("Réactions personnalisées")
+                .fields(fields)
+                .color(serenity::Colour::PURPLE),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("fr", "Teste quelle réaction se déclenche pour une phrase")
+)]
+async fn test(
+    ctx: Context<'_>,
+    #[description = "phrase à tester"] texte: String,
+) -> Result<(), PoiseError> {
+    let guild_id = ctx.guild_id().ok_or("pas de serveur")?;
+    let rules = reactions::guild_rules(ctx.serenity_context(), &ctx.data().db, guild_id).await?;
+
+    let info = MessageInfo { content: &texte };
+    let decisions = message::decide(&mut rand::thread_rng(), &info, &rules);
+
+    let content = if decisions.is_empty() {
+        "Aucune réaction ne se déclenche".to_owned()
+    } else {
+        let mut replied = false;
+        let lines: Vec<String> = decisions
+            .iter()
+            .map(|decision| {
+                let actions: Vec<String> = decision.actions.iter().map(describe_action).collect();
+                let ignored = if replied && decision.has_reply() {
+                    " (ignorée, une autre règle répond déjà)"
+                } else {
+                    ""
+                };
+                replied |= decision.has_reply();
+                format!("- {} : {}{ignored}", decision.rule, actions.join(", "))
+            })
+            .collect();
+        format!("Règles déclenchées :\n{}", lines.join("\n"))
+    };
+    ctx.say(content).await?;
+    Ok(())
+}
+
+fn split_list(s: &str, separator: char) -> Vec<String> {
+    s.split(separator)
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+fn describe_action(action: &Action) -> String {
+    match action {
+        Action::Reply(reply) => format!("répond \"{reply}\""),
+        Action::React(emoji) => format!("réagit {emoji}"),
+    }
+}
+
+fn describe(rule: &ReactionRule) -> String {
+    let mut lines = vec![
+        format!("Type : {}", rule.kind),
+        format!("Mots : {}", rule.words.join(", ")),
+    ];
+    if !rule.replies.is_empty() {
+        lines.push(format!("Réponses : {}", rule.replies.join(" | ")));
+    }
+    if !rule.emojis.is_empty() {
+        lines.push(format!("Emojis : {}", rule.emojis.join(" ")));
+    }
+    if rule.limits != Limits::ALWAYS {
+        lines.push(format!("Déclenchement : {}", rule.limits));
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,53 @@
+use crate::choices::{draw, parse_options};
+use crate::commands::{Context as PoiseContext, PoiseError};
+
+fn choose_intern(options: &str, count: Option<u32>) -> Result<String, anyhow::Error> {
+    let choices = parse_options(options, true)?;
+    let count = usize::try_from(count.unwrap_or(1))?;
+    let drawn = draw(&mut rand::thread_rng(), &choices, count);
+    Ok(match drawn.as_slice() {
+        [choice] => format!("Je choisis : **{}**", choice.label),
+        _ => drawn
+            .iter()
+            .enumerate()
+            .map(|(i, choice)| format!("{}. {}", i + 1, choice.label))
+            .collect::<Vec<String>>()
+            .join("\n"),
+    })
+}
+
+#[poise::command(
+    slash_command,
+    category = "general",
+    description_localized("fr", "Choisis parmi plusieurs options")
+)]
+pub async fn choose(
+    ctx: PoiseContext<'_>,
+    #[description = "options séparées par \"ou\", | ou des virgules, avec un poids optionnel : pizza:3"]
+    options: String,
+    #[description = "nombre d'options à tirer sans remise"]
+    #[min = 1]
+    nombre: Option<u32>,
+) -> Result<(), PoiseError> {
+    ctx.say(choose_intern(&options, nombre)?).await?;
+    Ok(())
+}
+
+#[poise::command(
+    prefix_command,
+    rename = "choose",
+    category = "general",
+    description_localized("fr", "Choisis parmi plusieurs options")
+)]
+pub async fn choose_prefix(
+    ctx: PoiseContext<'_>,
+    #[rest] options: String,
+) -> Result<(), PoiseError> {
+    // "$choose 2 pizza, sushi, kebab" draws two options
+    let (count, options) = match options.split_once(' ') {
+        Some((count, rest)) if count.parse::<u32>().is_ok() => (count.parse::<u32>().ok(), rest),
+        _ => (None, options.as_str()),
+    };
+    ctx.say(choose_intern(options, count)?).await?;
+    Ok(())
+}
@@ -1,6 +1,7 @@
 use crate::commands::{Context, PoiseError};
 use crate::message;
-use rand::seq::IteratorRandom;
+use crate::template::{self, Values};
+use poise::serenity_prelude::Mentionable;
 use rand::thread_rng;
 
 #[poise::command(
@@ -10,16 +11,17 @@ use rand::thread_rng;
     description_localized("fr", "Dis bonjour")
 )]
 pub async fn hello(ctx: Context<'_>) -> Result<(), PoiseError> {
-    ctx.say(salutation()).await?;
+    let channel = ctx.channel_id().mention().to_string();
+    let values = Values {
+        user: &ctx.author().name,
+        channel: &channel,
+        ..Values::default()
+    };
+    let content = template::render(&mut thread_rng(), &salutation(), &values);
+    ctx.say(content).await?;
     Ok(())
 }
 
 fn salutation() -> String {
-    format!(
-        "{} !",
-        message::SALUTATIONS
-            .iter()
-            .choose(&mut thread_rng())
-            .unwrap_or(&"Bonjour !")
-    )
+    format!("{} !", message::random_template(&message::SALUTATIONS))
 }
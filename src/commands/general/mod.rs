@@ -1,9 +1,17 @@
 pub mod based;
+pub mod choose;
 pub mod hello;
 pub mod help;
 pub mod id;
 pub mod nerd;
+pub mod oublie;
 pub mod ping;
+pub mod quote;
+pub mod remind;
 pub mod roll;
 pub mod slide;
-// pub mod tg;
+pub mod soiree;
+pub mod sondage;
+pub mod stats;
+pub mod teams;
+pub mod tg;
@@ -0,0 +1,23 @@
+use crate::commands::{Context, PoiseError};
+use crate::forget;
+
+#[poise::command(
+    slash_command,
+    ephemeral,
+    rename = "oublie-moi",
+    category = "general",
+    description_localized("fr", "Supprime toutes les données du bot vous concernant")
+)]
+pub async fn oublie_moi(ctx: Context<'_>) -> Result<(), PoiseError> {
+    let labels: Vec<&str> = forget::hooks().map(|(_, hook)| hook.label).collect();
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "Supprimer définitivement vos données sur tous les serveurs ({}) ?",
+                labels.join(", ")
+            ))
+            .components(forget::buttons(ctx.author().id)),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,208 @@
+use bson::doc;
+use poise::serenity_prelude::{
+    self as serenity, CreateAllowedMentions, CreateEmbed, CreateEmbedFooter,
+};
+use rand::seq::SliceRandom;
+
+use crate::commands::{Context, PoiseError};
+use crate::db;
+use crate::quotes::{self, Quote};
+use crate::utils::{self, is_admin};
+
+// quotes listed at most by search and user
+const MAX_LISTED: usize = 10;
+// length of a quote in lists, to fit in a message
+const MAX_CHARS: usize = 150;
+
+#[poise::command(
+    context_menu_command = "Sauvegarder la citation",
+    guild_only,
+    category = "general",
+    description_localized("fr", "Sauvegarde un message dans les citations du serveur")
+)]
+pub async fn quote_message(ctx: Context<'_>, message: serenity::Message) -> Result<(), PoiseError> {
+    let guild_id = ctx.guild_id().ok_or("pas de serveur")?;
+    let db = &ctx.data().db;
+
+    if message.content.is_empty() {
+        ctx.say("Ce message n'a pas de texte à citer").await?;
+        return Ok(());
+    }
+    let filter = doc! {"guild_id": guild_id.to_string(), "message_id": message.id.to_string()};
+    if let Some(quote) = db::find_filter::<Quote>(db, quotes::COLLECTION, filter).await? {
+        ctx.say(format!("Ce message est déjà la citation #{}", quote.number))
+            .await?;
+        return Ok(());
+    }
+
+    let author_name = utils::get_user_name(Some(guild_id), ctx.http(), &message.author).await;
+    let quote = Quote::builder(guild_id, &message, author_name, ctx.author().id);
+    let quote = quotes::insert(db, quote).await?;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content("Citation sauvegardée")
+            .embed(quote_embed(&quote)),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "general",
+    subcommands("random", "search", "user", "delete"),
+    subcommand_required,
+    description_localized("fr", "Les citations du serveur")
+)]
+pub async fn quote(_ctx: Context<'_>) -> Result<(), PoiseError> {
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    description_localized("fr", "Affiche une citation au hasard")
+)]
+async fn random(
+    ctx: Context<'_>,
+    #[description = "seulement les citations de cette personne"] personne: Option<serenity::User>,
+) -> Result<(), PoiseError> {
+    let guild_id = ctx.guild_id().ok_or("pas de serveur")?;
+    let all = quotes::guild_quotes(&ctx.data().db, guild_id).await?;
+    let candidates: Vec<&Quote> = all
+        .iter()
+        .filter(|q| {
+            personne
+                .as_ref()
+                .map_or(true, |user| q.author_id == user.id.to_string())
+        })
+        .collect();
+
+    let Some(quote) = candidates.choose(&mut rand::thread_rng()) else {
+        ctx.say("Aucune citation").await?;
+        return Ok(());
+    };
+    ctx.send(poise::CreateReply::default().embed(quote_embed(quote)))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    description_localized("fr", "Cherche une citation par son texte ou son auteur")
+)]
+async fn search(
+    ctx: Context<'_>,
+    #[description = "texte à chercher"] texte: String,
+) -> Result<(), PoiseError> {
+    let guild_id = ctx.guild_id().ok_or("pas de serveur")?;
+    let all = quotes::guild_quotes(&ctx.data().db, guild_id).await?;
+    let found = quotes::search(&all, &texte);
+    say_quotes(ctx, &found).await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    description_localized("fr", "Affiche les citations d'une personne")
+)]
+async fn user(
+    ctx: Context<'_>,
+    #[description = "auteur des citations"] personne: serenity::User,
+) -> Result<(), PoiseError> {
+    let guild_id = ctx.guild_id().ok_or("pas de serveur")?;
+    let all = quotes::guild_quotes(&ctx.data().db, guild_id).await?;
+    let found: Vec<&Quote> = all
+        .iter()
+        .filter(|q| q.author_id == personne.id.to_string())
+        .collect();
+    say_quotes(ctx, &found).await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("fr", "Supprime une citation")
+)]
+async fn delete(
+    ctx: Context<'_>,
+    #[description = "numéro de la citation"] numero: u32,
+) -> Result<(), PoiseError> {
+    let guild_id = ctx.guild_id().ok_or("pas de serveur")?;
+    let db = &ctx.data().db;
+
+    let filter = doc! {"guild_id": guild_id.to_string(), "number": numero};
+    let Some(quote) = db::find_filter::<Quote>(db, quotes::COLLECTION, filter).await? else {
+        ctx.say(format!("La citation #{numero} n'existe pas"))
+            .await?;
+        return Ok(());
+    };
+
+    // the quoted person, the one who saved it and admins can delete a quote
+    let author_id = ctx.author().id.to_string();
+    if quote.author_id != author_id && quote.saved_by != author_id && !is_admin(ctx).await {
+        ctx.say("Seuls l'auteur, la personne qui l'a sauvegardée et les admins peuvent supprimer cette citation")
+            .await?;
+        return Ok(());
+    }
+    db::delete(db, quotes::COLLECTION, &quote).await?;
+    ctx.say(format!("La citation #{numero} a bien été supprimée"))
+        .await?;
+    Ok(())
+}
+
+fn quote_embed(quote: &Quote) -> CreateEmbed {
+    CreateEmbed::new()
+        .description(format!(
+            "« {} »\n\n[Message original]({})",
+            quote.content,
+            quote.link()
+        ))
+        .title(format!("— {}", quote.author_name))
+        .footer(CreateEmbedFooter::new(format!(
+            "Citation #{} du {}",
+            quote.number,
+            quote.at.format("%d/%m/%Y")
+        )))
+        .color(serenity::Colour::PURPLE)
+}
+
+// the quoted messages can contain mentions, they must not ping again
+async fn say_quotes(ctx: Context<'_>, quotes: &[&Quote]) -> Result<(), PoiseError> {
+    ctx.send(
+        poise::CreateReply::default()
+            .content(list(quotes))
+            .allowed_mentions(CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
+
+fn list(quotes: &[&Quote]) -> String {
+    if quotes.is_empty() {
+        return "Aucune citation".to_owned();
+    }
+    let mut lines: Vec<String> = quotes
+        .iter()
+        .take(MAX_LISTED)
+        .map(|q| {
+            let content: String = q.content.chars().take(MAX_CHARS).collect();
+            let cut = if content.len() < q.content.len() {
+                "…"
+            } else {
+                ""
+            };
+            format!("#{} « {content}{cut} » — {}", q.number, q.author_name)
+        })
+        .collect();
+    if quotes.len() > MAX_LISTED {
+        lines.push(format!("… et {} autres", quotes.len() - MAX_LISTED));
+    }
+    lines.join("\n")
+}
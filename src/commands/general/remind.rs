@@ -0,0 +1,97 @@
+use bson::doc;
+
+use crate::commands::{Context, PoiseError};
+use crate::datetime;
+use crate::db;
+use crate::reminders::{self, Reminder};
+
+#[poise::command(
+    slash_command,
+    category = "general",
+    description_localized("fr", "Programme un rappel")
+)]
+pub async fn remindme(
+    ctx: Context<'_>,
+    #[description = "quand : 2h30, demain 18h, à 18h, lundi 20h, 25/12 18h"] quand: String,
+    #[description = "texte du rappel"] texte: String,
+    #[description = "rappel en message privé plutôt que dans ce salon"] dm: Option<bool>,
+) -> Result<(), PoiseError> {
+    let due = match datetime::parse(&quand, datetime::now()) {
+        Ok(due) => due,
+        Err(e) => {
+            ctx.send(
+                poise::CreateReply::default()
+                    .content(format!("Erreur : {e}"))
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let db = &ctx.data().db;
+    let user_id = ctx.author().id;
+    let channel_id = (!dm.unwrap_or(false)).then(|| ctx.channel_id());
+    let reminder = Reminder::builder(user_id, channel_id, texte, due);
+    let number = reminders::insert(db, reminder).await?.number;
+
+    let timestamp = due.timestamp();
+    ctx.say(format!(
+        "Rappel #{number} programmé pour le <t:{timestamp}:F> (<t:{timestamp}:R>)"
+    ))
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    category = "general",
+    subcommands("list", "cancel"),
+    subcommand_required,
+    description_localized("fr", "Gère vos rappels")
+)]
+pub async fn remind(_ctx: Context<'_>) -> Result<(), PoiseError> {
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    ephemeral,
+    description_localized("fr", "Affiche vos rappels à venir")
+)]
+async fn list(ctx: Context<'_>) -> Result<(), PoiseError> {
+    let reminders = reminders::user_reminders(&ctx.data().db, ctx.author().id).await?;
+    let content = if reminders.is_empty() {
+        "Aucun rappel programmé".to_owned()
+    } else {
+        reminders
+            .iter()
+            .map(|r| format!("#{} <t:{}:F> : {}", r.number, r.due.timestamp(), r.text))
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+    ctx.say(content).await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    ephemeral,
+    description_localized("fr", "Annule un rappel")
+)]
+async fn cancel(
+    ctx: Context<'_>,
+    #[description = "numéro du rappel"] numero: u32,
+) -> Result<(), PoiseError> {
+    let db = &ctx.data().db;
+    let filter = doc! {"user_id": ctx.author().id.to_string(), "number": numero};
+    let content = match db::find_filter::<Reminder>(db, reminders::COLLECTION, filter).await? {
+        Some(reminder) => {
+            db::delete(db, reminders::COLLECTION, &reminder).await?;
+            format!("Le rappel #{numero} a bien été annulé")
+        }
+        None => format!("Le rappel #{numero} n'existe pas"),
+    };
+    ctx.say(content).await?;
+    Ok(())
+}
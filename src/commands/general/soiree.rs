@@ -0,0 +1,107 @@
+use bson::doc;
+use tracing::error;
+
+use crate::commands::{Context, PoiseError};
+use crate::game_nights::{self, GameNight};
+use crate::utils::is_admin;
+use crate::{datetime, db};
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "soirée",
+    category = "general",
+    subcommands("create", "annuler"),
+    subcommand_required,
+    description_localized("fr", "Organise des soirées jeux")
+)]
+pub async fn soiree(_ctx: Context<'_>) -> Result<(), PoiseError> {
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    description_localized("fr", "Crée une soirée avec inscriptions")
+)]
+async fn create(
+    ctx: Context<'_>,
+    #[description = "jeu de la soirée"] jeu: String,
+    #[description = "date : vendredi 21h, demain 20h30, 25/12 18h"] date: String,
+    #[description = "répéter la soirée toutes les semaines"] hebdo: Option<bool>,
+) -> Result<(), PoiseError> {
+    let starts_at = match datetime::parse(&date, datetime::now()) {
+        Ok(at) => at,
+        Err(e) => {
+            ctx.send(
+                poise::CreateReply::default()
+                    .content(format!("Erreur : {e}"))
+                    .ephemeral(true),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let mut night = GameNight::builder(
+        ctx.guild_id().map(|id| id.to_string()),
+        ctx.channel_id().to_string(),
+        jeu,
+        ctx.author().id.to_string(),
+        starts_at,
+        hebdo.unwrap_or(false),
+    );
+    let reply = ctx
+        .send(
+            poise::CreateReply::default()
+                .embed(night.embed())
+                .components(night.buttons()),
+        )
+        .await?;
+    night.message_id = reply.message().await?.id.to_string();
+    let _ = db::insert(&ctx.data().db, game_nights::COLLECTION, &night).await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("fr", "Annule les prochaines soirées d'un jeu")
+)]
+async fn annuler(
+    ctx: Context<'_>,
+    #[description = "jeu de la soirée"] jeu: String,
+) -> Result<(), PoiseError> {
+    let guild_id = ctx.guild_id().ok_or("pas de serveur")?;
+    let db = &ctx.data().db;
+
+    let filter = doc! {"guild_id": guild_id.to_string(), "game": &jeu};
+    let nights: Vec<GameNight> = db::get_objects(db, game_nights::COLLECTION, filter).await?;
+    if nights.is_empty() {
+        ctx.say(format!("Aucune soirée {jeu} de prévue")).await?;
+        return Ok(());
+    }
+
+    // the organizer and admins can cancel a night
+    let author_id = ctx.author().id.to_string();
+    let admin = is_admin(ctx).await;
+    let mut cancelled = 0;
+    for night in nights
+        .iter()
+        .filter(|n| admin || n.organizer_id == author_id)
+    {
+        db::delete(db, game_nights::COLLECTION, night).await?;
+        if let Err(e) = game_nights::close_message(ctx.serenity_context(), night).await {
+            error!("error while closing the message of a cancelled game night : {e}");
+        }
+        cancelled += 1;
+    }
+    let content = if cancelled == 0 {
+        "Seuls l'organisateur et les admins peuvent annuler une soirée".to_owned()
+    } else {
+        format!("{cancelled} soirée(s) {jeu} annulée(s)")
+    };
+    ctx.say(content).await?;
+    Ok(())
+}
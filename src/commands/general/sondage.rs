@@ -0,0 +1,84 @@
+use bson::doc;
+use tracing::error;
+
+use crate::commands::{Context, PoiseError};
+use crate::datetime;
+use crate::db;
+use crate::polls::{self, Poll};
+
+#[poise::command(
+    slash_command,
+    category = "general",
+    description_localized("fr", "Crée un sondage avec des boutons")
+)]
+pub async fn sondage(
+    ctx: Context<'_>,
+    // the question is the title of the embed, limited by discord
+    #[description = "question du sondage"]
+    #[max_length = 256]
+    question: String,
+    #[description = "options séparées par des | ou des virgules"] options: String,
+    #[description = "fin du sondage : 2h, demain 18h, vendredi 12h"] fin: Option<String>,
+) -> Result<(), PoiseError> {
+    let options = polls::parse_options(&options);
+    let error = if options.len() < 2 {
+        Some("il faut au moins deux options".to_owned())
+    } else if options.len() > polls::MAX_OPTIONS {
+        Some(format!("il faut au plus {} options", polls::MAX_OPTIONS))
+    } else {
+        None
+    };
+    let closes_at = match fin.map(|fin| datetime::parse(&fin, datetime::now())) {
+        Some(Ok(at)) => Some(at),
+        Some(Err(e)) => return say_error(ctx, &e.to_string()).await,
+        None => None,
+    };
+    if let Some(error) = error {
+        return say_error(ctx, &error).await;
+    }
+
+    let poll = Poll::builder(
+        ctx.guild_id().map(|id| id.to_string()),
+        ctx.channel_id().to_string(),
+        question,
+        options,
+        closes_at,
+    );
+    // stored before the buttons are sent, so that the first votes find it
+    let db = &ctx.data().db;
+    let _ = db::insert(db, polls::COLLECTION, &poll).await?;
+    let sent = match ctx
+        .send(
+            poise::CreateReply::default()
+                .embed(poll.embed())
+                .components(poll.buttons()),
+        )
+        .await
+    {
+        Ok(reply) => reply.message().await.map(|message| message.id),
+        Err(e) => Err(e),
+    };
+    let message_id = match sent {
+        Ok(message_id) => message_id,
+        Err(e) => {
+            // nobody can vote without the buttons
+            if let Err(e) = db::delete(db, polls::COLLECTION, &poll).await {
+                error!("error while deleting the unsent poll {} : {e}", poll.id());
+            }
+            return Err(e.into());
+        }
+    };
+    let update = doc! {"$set": {"message_id": message_id.to_string()}};
+    db::update(db, polls::COLLECTION, &poll, &update).await?;
+    Ok(())
+}
+
+async fn say_error(ctx: Context<'_>, error: &str) -> Result<(), PoiseError> {
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!("Erreur : {error}"))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
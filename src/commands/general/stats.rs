@@ -0,0 +1,101 @@
+use chrono::Utc;
+use poise::serenity_prelude::{self as serenity, CreateEmbed};
+
+use crate::analytics;
+use crate::commands::{Context, PoiseError};
+
+// number of lines in the rankings
+const TOP: usize = 5;
+const TREND_BAR: &str = "█";
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "general",
+    subcommands("reactions"),
+    subcommand_required,
+    description_localized("fr", "Statistiques du serveur")
+)]
+pub async fn stats(_ctx: Context<'_>) -> Result<(), PoiseError> {
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "réactions",
+    description_localized("fr", "Les réactions automatiques les plus déclenchées")
+)]
+async fn reactions(
+    ctx: Context<'_>,
+    #[description = "nombre de semaines à afficher (8 par défaut)"]
+    #[min = 1]
+    #[max = 52]
+    semaines: Option<u32>,
+) -> Result<(), PoiseError> {
+    let guild_id = ctx.guild_id().ok_or("pas de serveur")?;
+    let weeks = semaines.unwrap_or(8);
+    let now = Utc::now();
+    let since = analytics::since(weeks, now);
+    let firings = analytics::guild_firings(&ctx.data().db, guild_id, since).await?;
+
+    if firings.is_empty() {
+        ctx.say("Aucune réaction déclenchée sur cette période")
+            .await?;
+        return Ok(());
+    }
+
+    let summary = analytics::summarize(&firings, weeks, now);
+    let rules = summary
+        .rules
+        .iter()
+        .take(TOP)
+        .map(|(rule, count)| format!("{} : {count}", rule_name(rule)))
+        .collect::<Vec<String>>()
+        .join("\n");
+    let feured = if summary.feured.is_empty() {
+        "Personne".to_owned()
+    } else {
+        summary
+            .feured
+            .iter()
+            .take(TOP)
+            .map(|(user_id, count)| format!("<@{user_id}> : {count}"))
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+    let max = summary
+        .weeks
+        .iter()
+        .map(|(_, count)| *count)
+        .max()
+        .unwrap_or(0);
+    let trend = summary
+        .weeks
+        .iter()
+        .map(|(week, count)| {
+            // bars of at most 10 blocks
+            let bar = TREND_BAR.repeat((count * 10).div_ceil(max.max(1)));
+            format!("{} : {bar} {count}", week.format("%d/%m"))
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::new()
+                .title(format!("Réactions des {weeks} dernières semaines"))
+                .field("Règles les plus déclenchées", rules, false)
+                .field("Les plus feurés", feured, false)
+                .field("Par semaine", trend, false)
+                .color(serenity::Colour::PURPLE),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+// guild rules are stored as "guild_id:name"
+fn rule_name(rule: &str) -> &str {
+    rule.split_once(':').map_or(rule, |(_, name)| name)
+}
@@ -0,0 +1,178 @@
+use std::sync::OnceLock;
+
+use itertools::Itertools;
+use poise::serenity_prelude::{self as serenity, CreateEmbed, UserId};
+
+use crate::commands::{Context, PoiseError};
+use crate::ratings::{self, Team};
+use crate::text;
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    category = "general",
+    description_localized("fr", "Fait des équipes équilibrées selon le niveau de chacun")
+)]
+pub async fn teams(
+    ctx: Context<'_>,
+    #[description = "jeu, pour utiliser les niveaux de ce jeu"] jeu: String,
+    #[description = "nombre d'équipes"]
+    #[min = 2]
+    #[max = 10]
+    equipes: u32,
+    #[description = "joueurs mentionnés : @a @b @c"] joueurs: Option<String>,
+    #[description = "salon vocal dont les membres jouent"]
+    #[channel_types("Voice")]
+    salon: Option<serenity::GuildChannel>,
+) -> Result<(), PoiseError> {
+    let guild_id = ctx.guild_id().ok_or("pas de serveur")?;
+    let mut players = joueurs.as_deref().map(mentions).unwrap_or_default();
+    if let Some(salon) = salon {
+        players.extend(voice_members(ctx, salon.id));
+    }
+    players.sort_unstable();
+    players.dedup();
+
+    let count = usize::try_from(equipes)?;
+    if players.len() < count {
+        ctx.say(format!(
+            "Il faut au moins {count} joueurs, mentionnez-les ou choisissez un salon vocal"
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let game = game_key(&jeu);
+    let rated = ratings::get(&ctx.data().db, guild_id, &game, &players).await?;
+    let teams = ratings::balance(&rated, count);
+
+    let mut embed = CreateEmbed::new()
+        .title(format!("Équipes pour {jeu}"))
+        .color(serenity::Colour::PURPLE);
+    for (i, team) in teams.iter().enumerate() {
+        embed = embed.field(
+            format!("Équipe {} ({:.0})", i + 1, ratings::total(team)),
+            team_lines(team),
+            true,
+        );
+    }
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "match",
+    category = "general",
+    subcommands("result"),
+    subcommand_required,
+    description_localized("fr", "Résultats des matchs")
+)]
+pub async fn match_(_ctx: Context<'_>) -> Result<(), PoiseError> {
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    description_localized("fr", "Enregistre le résultat d'un match et met à jour les niveaux")
+)]
+async fn result(
+    ctx: Context<'_>,
+    #[description = "jeu du match"] jeu: String,
+    #[description = "gagnants mentionnés : @a @b"] gagnants: String,
+    #[description = "perdants mentionnés : @c @d"] perdants: String,
+) -> Result<(), PoiseError> {
+    let guild_id = ctx.guild_id().ok_or("pas de serveur")?;
+    let db = &ctx.data().db;
+    let winners = mentions(&gagnants);
+    let losers = mentions(&perdants);
+    if winners.is_empty() || losers.is_empty() || winners.iter().any(|w| losers.contains(w)) {
+        ctx.say("Il faut des gagnants et des perdants différents, mentionnés avec @")
+            .await?;
+        return Ok(());
+    }
+
+    let game = game_key(&jeu);
+    let winners = ratings::get(db, guild_id, &game, &winners).await?;
+    let losers = ratings::get(db, guild_id, &game, &losers).await?;
+    let values = |team: &Team| -> Vec<f64> { team.iter().map(|(_, rating)| *rating).collect() };
+    let delta = ratings::elo_delta(&values(&winners), &values(&losers));
+
+    for (user_id, _) in &winners {
+        ratings::add(db, guild_id, &game, *user_id, delta).await?;
+    }
+    for (user_id, _) in &losers {
+        ratings::add(db, guild_id, &game, *user_id, -delta).await?;
+    }
+
+    let changes = |team: &Team, delta: f64| -> String {
+        team.iter()
+            .map(|(user_id, rating)| format!("<@{user_id}> : {rating:.0} → {:.0}", rating + delta))
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::new()
+                .title(format!("Match de {jeu} enregistré"))
+                .field("Gagnants", changes(&winners, delta), true)
+                .field("Perdants", changes(&losers, -delta), true)
+                .color(serenity::Colour::PURPLE),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+// "LoL" and "lol" are the same game
+fn game_key(game: &str) -> String {
+    text::normalize(game.trim())
+}
+
+// each player once, even if mentioned several times
+fn mentions(s: &str) -> Vec<UserId> {
+    static MENTION: OnceLock<regex::Regex> = OnceLock::new();
+    text::regex(&MENTION, r"<@!?(\d+)>")
+        .captures_iter(s)
+        .filter_map(|caps| caps[1].parse::<u64>().ok())
+        .filter(|id| *id != 0)
+        .unique()
+        .map(UserId::new)
+        .collect()
+}
+
+fn voice_members(ctx: Context<'_>, channel_id: serenity::ChannelId) -> Vec<UserId> {
+    ctx.guild().map_or_else(Vec::new, |guild| {
+        guild
+            .voice_states
+            .values()
+            .filter(|state| state.channel_id == Some(channel_id))
+            .map(|state| state.user_id)
+            .collect()
+    })
+}
+
+fn team_lines(team: &[(UserId, f64)]) -> String {
+    team.iter()
+        .map(|(user_id, rating)| format!("<@{user_id}> ({rating:.0})"))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mentions() {
+        assert_eq!(
+            mentions("<@1> et <@!2>, pas <#3>"),
+            vec![UserId::new(1), UserId::new(2)]
+        );
+        // a player listed twice only counts once
+        assert_eq!(mentions("<@1> <@!1>"), vec![UserId::new(1)]);
+        assert_eq!(game_key(" LoL "), "lol");
+    }
+}
@@ -1,178 +1,133 @@
-use serenity::{
-    builder::CreateApplicationCommand,
-    model::prelude::{
-        command::CommandOptionType, interaction::application_command::ApplicationCommandInteraction,
-    },
-    prelude::Context,
-};
+use tracing::error;
 
-use crate::{db, utils};
-use crate::{InteractionMessage, Response};
+use crate::{
+    commands::{Context, PoiseError},
+    mute::{self, Target},
+    utils::is_admin,
+};
 
-async fn toggle_mute<
-    T: core::fmt::Debug
-        + serde::de::DeserializeOwned
-        + serde::Serialize
-        + std::marker::Unpin
-        + std::marker::Send
-        + std::marker::Sync,
->(
-    ctx: &Context,
-    collection: &str,
-    object: T,
-) -> Result<bool, mongodb::error::Error> {
-    if db::is_object_in_coll(ctx, collection, &object).await? {
-        db::delete(ctx, collection, &object).await?;
-        Ok(true)
-    } else {
-        db::insert(ctx, collection, &object).await?;
-        Ok(false)
-    }
+#[poise::command(
+    slash_command,
+    category = "general",
+    subcommands("moi", "chan", "serv", "status"),
+    subcommand_required,
+    description_localized("fr", "Toggle les réponses du bot")
+)]
+pub async fn tg(_ctx: Context<'_>) -> Result<(), PoiseError> {
+    Ok(())
 }
 
-fn check_muted_str(check: Result<bool, mongodb::error::Error>) -> String {
-    match check {
-        Ok(c) => {
-            if c {
-                String::from(":mute:")
-            } else {
-                String::from(":loud_sound:")
-            }
+#[poise::command(
+    slash_command,
+    ephemeral,
+    description_localized("fr", "Toggle les réponses du bot à vos messages")
+)]
+async fn moi(ctx: Context<'_>) -> Result<(), PoiseError> {
+    let content = match mute::toggle(
+        ctx.serenity_context(),
+        &ctx.data().db,
+        Target::User(ctx.author().id),
+    )
+    .await
+    {
+        Ok(true) => String::from("Le bot répondra à vos messages"),
+        Ok(false) => String::from("Le bot ne répondra plus à vos messages"),
+        Err(e) => {
+            error!("error while toggling mute : {e}");
+            e.reply().to_owned()
         }
-        Err(e) => format!(":x: (Erreur : {e}"),
-    }
+    };
+    ctx.say(content).await?;
+    Ok(())
 }
 
-async fn mute_status(ctx: &Context, command: &ApplicationCommandInteraction) -> String {
-    let user_muted = db::is_object_in_coll(
-        ctx,
-        "mute_users",
-        &db::User::builder(command.user.id.to_string()),
-    )
-    .await;
-    let chan_muted = db::is_object_in_coll(
-        ctx,
-        "mute_chans",
-        &db::Chan::builder(command.channel_id.to_string()),
-    )
-    .await;
-    let guild_muted = if let Some(guild_id) = command.guild_id {
-        db::is_object_in_coll(
-            ctx,
-            "mute_guilds",
-            &db::Guild::builder(guild_id.to_string()),
+#[poise::command(
+    slash_command,
+    ephemeral,
+    description_localized("fr", "Toggle les réponses du bot aux messages du chan")
+)]
+async fn chan(ctx: Context<'_>) -> Result<(), PoiseError> {
+    let content = if is_admin(ctx).await {
+        match mute::toggle(
+            ctx.serenity_context(),
+            &ctx.data().db,
+            Target::Chan(ctx.channel_id()),
         )
         .await
+        {
+            Ok(true) => String::from("Le bot répondra aux messages de ce chan"),
+            Ok(false) => String::from("Le bot ne répondra plus aux messages de ce chan"),
+            Err(e) => {
+                error!("error while toggling mute : {e}");
+                e.reply().to_owned()
+            }
+        }
     } else {
-        Ok(false)
+        String::from("Vous devez être admin pour utiliser cette commande")
     };
-    format!(
-        "Utilisateur muted : {}\nChan muted : {}\nServeur muted : {}",
-        check_muted_str(user_muted),
-        check_muted_str(chan_muted),
-        check_muted_str(guild_muted)
-    )
+    ctx.say(content).await?;
+    Ok(())
 }
 
-pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) -> Response {
-    let Some(subcommand) = command.data.options.first() else {
-        return Response::Message(InteractionMessage::ephemeral("Erreur : pas de sous-commandes"))
-    };
-    let content = match subcommand.name.as_str() {
-        "moi" => {
-            match toggle_mute(
-                ctx,
-                "mute_users",
-                db::User::builder(command.user.id.to_string()),
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("fr", "Toggle les réponses du bot aux messages du serveur")
+)]
+async fn serv(ctx: Context<'_>) -> Result<(), PoiseError> {
+    let content = match ctx.guild_id() {
+        Some(guild_id) if is_admin(ctx).await => {
+            match mute::toggle(
+                ctx.serenity_context(),
+                &ctx.data().db,
+                Target::Guild(guild_id),
             )
             .await
             {
-                Ok(b) => {
-                    if b {
-                        String::from("Le bot répondra à vos messages")
-                    } else {
-                        String::from("Le bot ne répondra plus à vos messages")
-                    }
-                }
-                Err(e) => format!("Erreur : {e}"),
-            }
-        }
-        "chan" => {
-            if utils::admin_command(command) {
-                match toggle_mute(
-                    ctx,
-                    "mute_chans",
-                    db::Chan::builder(command.channel_id.to_string()),
-                )
-                .await
-                {
-                    Ok(b) => {
-                        if b {
-                            String::from("Le bot répondra aux messages de ce chan")
-                        } else {
-                            String::from("Le bot ne répondra plus aux messages de ce chan")
-                        }
-                    }
-                    Err(e) => format!("Erreur : {e}"),
-                }
-            } else {
-                String::from("Vous devez être admin pour utiliser cette commande")
-            }
-        }
-        "serv" => {
-            if command.guild_id.is_some() && utils::admin_command(command) {
-                if let Some(guild_id) = command.guild_id {
-                    match toggle_mute(ctx, "mute_guilds", db::Guild::builder(guild_id.to_string()))
-                        .await
-                    {
-                        Ok(b) => {
-                            if b {
-                                String::from("Le bot répondra aux messages de ce serveur")
-                            } else {
-                                String::from("Le bot ne répondra plus aux messages de ce serveur")
-                            }
-                        }
-                        Err(e) => format!("Erreur : {e}"),
-                    }
-                } else {
-                    "pas de guild_id".to_owned()
+                Ok(true) => String::from("Le bot répondra aux messages de ce serveur"),
+                Ok(false) => String::from("Le bot ne répondra plus aux messages de ce serveur"),
+                Err(e) => {
+                    error!("error while toggling mute : {e}");
+                    e.reply().to_owned()
                 }
-            } else {
-                String::from("Vous devez être admin pour utiliser cette commande")
             }
         }
-        "status" => mute_status(ctx, command).await,
-        _ => String::from("unexpected subcommand"),
+        Some(_) => String::from("Vous devez être admin pour utiliser cette commande"),
+        None => "pas de guild_id".to_owned(),
     };
-    Response::Message(InteractionMessage::ephemeral(content))
+    ctx.say(content).await?;
+    Ok(())
 }
 
-pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
-    command
-        .name("tg")
-        .description("Toggle les réponses du bot")
-        .create_option(|option| {
-            option
-                .name("moi")
-                .description("Toggle les réponses du bot à vos messages")
-                .kind(CommandOptionType::SubCommand)
-        })
-        .create_option(|option| {
-            option
-                .name("chan")
-                .description("Toggle les réponses du bot aux messages du chan")
-                .kind(CommandOptionType::SubCommand)
-        })
-        .create_option(|option| {
-            option
-                .name("serv")
-                .description("Toggle les réponses du bot aux messages du serveur")
-                .kind(CommandOptionType::SubCommand)
-        })
-        .create_option(|option| {
-            option
-                .name("status")
-                .description("Affiche les informations sur les réponses du bot ici")
-                .kind(CommandOptionType::SubCommand)
-        })
+#[poise::command(
+    slash_command,
+    ephemeral,
+    description_localized("fr", "Affiche les informations sur les réponses du bot ici")
+)]
+async fn status(ctx: Context<'_>) -> Result<(), PoiseError> {
+    let sctx = ctx.serenity_context();
+    let user_muted = mute::is_muted(sctx, Target::User(ctx.author().id)).await;
+    let chan_muted = mute::is_muted(sctx, Target::Chan(ctx.channel_id())).await;
+    let guild_muted = if let Some(guild_id) = ctx.guild_id() {
+        mute::is_muted(sctx, Target::Guild(guild_id)).await
+    } else {
+        false
+    };
+    ctx.say(format!(
+        "Utilisateur muted : {}\nChan muted : {}\nServeur muted : {}",
+        muted_str(user_muted),
+        muted_str(chan_muted),
+        muted_str(guild_muted)
+    ))
+    .await?;
+    Ok(())
+}
+
+fn muted_str(muted: bool) -> &'static str {
+    if muted {
+        ":mute:"
+    } else {
+        ":loud_sound:"
+    }
 }
@@ -4,50 +4,7 @@ use bson::doc;
 use serenity::framework::standard::CommandError;
 use serenity::{framework::standard::Args, model::prelude::Message, prelude::Context};
 
-#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
-pub struct Macro {
-    _id: mongodb::bson::oid::ObjectId,
-    user_id: String,
-    pub name: String,
-    pub command: String,
-    pub args: Option<String>,
-}
-
-impl Macro {
-    pub fn builder(user_id: String, name: String, command: String, args: Option<String>) -> Macro {
-        Macro {
-            _id: mongodb::bson::oid::ObjectId::new(),
-            user_id,
-            name,
-            command,
-            args,
-        }
-    }
-
-    pub fn edit(mut self, args: Option<&String>) -> Self {
-        self.args = args.cloned();
-        self
-    }
-}
-
-#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
-pub struct TempMacro {
-    _id: mongodb::bson::oid::ObjectId,
-    pub user_id: String,
-    pub command: String,
-    pub args: Option<String>,
-}
-
-impl TempMacro {
-    pub fn builder(user_id: String, command: String, args: Option<String>) -> TempMacro {
-        TempMacro {
-            _id: mongodb::bson::oid::ObjectId::new(),
-            user_id,
-            command,
-            args,
-        }
-    }
-}
+pub use super::model::{Macro, TempMacro};
 
 pub async fn handle_macro(ctx: &Context, msg: &Message) -> String {
     let Some(strip_content) = msg.content.strip_prefix('!') else {
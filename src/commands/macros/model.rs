@@ -0,0 +1,153 @@
+use std::time::Duration;
+
+use bson::{doc, Document};
+use chrono::{DateTime, Utc};
+use futures::FutureExt;
+use mongodb::bson::oid::ObjectId;
+use serenity::model::prelude::UserId;
+
+use crate::collections::{check, Collection};
+use crate::forget::Hook;
+use crate::repository::{ByUser, Repository};
+
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+pub struct Macro {
+    _id: ObjectId,
+    user_id: String,
+    pub name: String,
+    pub command: String,
+    pub args: Option<String>,
+}
+
+impl Macro {
+    pub fn builder(user_id: String, name: String, command: String, args: Option<String>) -> Macro {
+        Macro {
+            _id: ObjectId::new(),
+            user_id,
+            name,
+            command,
+            args,
+        }
+    }
+
+    pub fn edit(mut self, args: Option<&String>) -> Self {
+        self.args = args.cloned();
+        self
+    }
+}
+
+// macro waiting for its name, created from a message
+#[derive(Debug, serde::Deserialize, serde::Serialize, Clone)]
+pub struct TempMacro {
+    _id: ObjectId,
+    pub user_id: String,
+    pub command: String,
+    pub args: Option<String>,
+    // abandoned ones expire, see COLLECTIONS
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl TempMacro {
+    pub fn builder(user_id: String, command: String, args: Option<String>) -> TempMacro {
+        TempMacro {
+            _id: ObjectId::new(),
+            user_id,
+            command,
+            args,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+pub enum MacroFilter {
+    User(UserId),
+    Named(UserId, String),
+}
+
+impl From<MacroFilter> for Document {
+    fn from(filter: MacroFilter) -> Self {
+        match filter {
+            MacroFilter::User(id) => doc! {"user_id": id.to_string()},
+            MacroFilter::Named(id, name) => doc! {"user_id": id.to_string(), "name": name},
+        }
+    }
+}
+
+pub struct Macros;
+
+impl Repository for Macros {
+    type Model = Macro;
+    type Filter = MacroFilter;
+    const COLLECTION: &'static str = "macros";
+}
+
+pub struct TempMacros;
+
+impl Repository for TempMacros {
+    type Model = TempMacro;
+    type Filter = ByUser;
+    const COLLECTION: &'static str = "temp_macros";
+}
+
+pub const COLLECTIONS: &[Collection] = &[
+    Collection {
+        name: Macros::COLLECTION,
+        model: check::<Macro>,
+        unique: &[&["user_id", "name"]],
+        ttl: None,
+        forget: Some(Hook {
+            label: "macros",
+            forget: |db, user_id| Macros::delete_many(db, MacroFilter::User(user_id)).boxed(),
+        }),
+    },
+    Collection {
+        name: TempMacros::COLLECTION,
+        model: check::<TempMacro>,
+        unique: &[&["user_id"]],
+        // macros waiting for a name from an abandoned modal
+        ttl: Some(("created_at", Duration::from_secs(60 * 60))),
+        forget: Some(Hook {
+            label: "macros en cours de création",
+            forget: |db, user_id| TempMacros::delete_many(db, ByUser(user_id)).boxed(),
+        }),
+    },
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::Db;
+
+    #[tokio::test]
+    async fn test_macros() {
+        let db = Db::memory();
+        let user = UserId::new(10);
+        let init = Macro::builder("10".to_owned(), "init".to_owned(), "roll".to_owned(), None);
+        Macros::insert(&db, &init).await.unwrap();
+        assert!(Macros::insert(&db, &init).await.is_err());
+        let d6 = Macro::builder("10".to_owned(), "d6".to_owned(), "roll".to_owned(), None);
+        Macros::insert(&db, &d6).await.unwrap();
+
+        let named = |name: &str| MacroFilter::Named(user, name.to_owned());
+        let args = "d20+4".to_owned();
+        Macros::update(&db, &init, &doc! {"$set": {"args": &args}})
+            .await
+            .unwrap();
+        let found = Macros::find_one(&db, named("init")).await.unwrap().unwrap();
+        assert_eq!(found.args, Some(args));
+
+        Macros::delete_one(&db, named("d6")).await.unwrap();
+        assert_eq!(
+            Macros::find(&db, MacroFilter::User(user))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        Macros::delete_many(&db, MacroFilter::User(user))
+            .await
+            .unwrap();
+        assert!(Macros::all(&db).await.unwrap().is_empty());
+    }
+}
@@ -2,8 +2,11 @@ use crate::Data;
 
 pub mod admin;
 pub mod general;
-// pub mod macros;
-// pub mod pdx;
+// only the models are compiled, the commands still use the old framework
+#[allow(dead_code)]
+pub mod macros;
+#[allow(dead_code)]
+pub mod pdx;
 
 // TODO: better Error
 pub type PoiseError = Box<dyn std::error::Error + Send + Sync>;
@@ -1,6 +1,7 @@
-use super::model::{PdxGame, PdxLinks};
+use super::model::{PdxGame, PdxLinks, PdxLinksRepo};
 use crate::interaction::Response;
-use crate::{db, utils, web_scraper};
+use crate::repository::Repository;
+use crate::{utils, web_scraper};
 use serenity::framework::standard::CommandError;
 use serenity::framework::standard::{macros::command, Args, CommandResult};
 use serenity::model::prelude::interaction::application_command::ApplicationCommandInteraction;
@@ -63,8 +64,8 @@ async fn update_links(
         for (game, link) in new_links {
             new_pdx.update(game, link)?;
         }
-        db::delete(ctx, "pdx_links", &pdx).await?;
-        let _: bson::Bson = db::insert(ctx, "pdx_links", &new_pdx).await?;
+        // same _id, so the links are never missing from the db
+        PdxLinksRepo::replace(ctx, &new_pdx).await?;
         Ok(new_pdx)
     }
 }
@@ -1,14 +1,14 @@
-use serenity::framework::standard::macros::group;
+// use serenity::framework::standard::macros::group;
 
-pub mod dd;
+// pub mod dd;
 pub mod model;
-pub mod setup;
-pub mod show;
+// pub mod setup;
+// pub mod show;
 
-use dd::DD_COMMAND;
-use show::SHOW_COMMAND;
+// use dd::DD_COMMAND;
+// use show::SHOW_COMMAND;
 
-#[group]
-#[prefix = "pdx"]
-#[commands(dd, show)]
-struct Pdx;
+// #[group]
+// #[prefix = "pdx"]
+// #[commands(dd, show)]
+// struct Pdx;
@@ -1,7 +1,10 @@
+use futures::FutureExt;
 use mongodb::bson::oid::ObjectId;
-use serenity::{framework::standard::CommandError, prelude::*};
 
-use crate::{db, utils};
+use crate::collections::{check, Collection};
+use crate::db::{Db, DbError};
+use crate::forget::Hook;
+use crate::repository::{Any, ByUser, Repository};
 
 #[derive(
     Debug, serde::Deserialize, serde::Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
@@ -76,7 +79,7 @@ pub struct GameLinks {
 
 impl GameLinks {
     pub fn update(&mut self, new_link: String) -> &mut Self {
-        self.previous = self.latest.clone();
+        self.previous.clone_from(&self.latest);
         self.latest = new_link;
         self
     }
@@ -117,10 +120,10 @@ impl PdxLinks {
             .collect()
     }
 
-    pub async fn db_links(ctx: &Context) -> Result<Self, CommandError> {
-        db::find_filter(ctx, "pdx_links", None)
+    pub async fn db_links(db: &Db) -> Result<Self, DbError> {
+        PdxLinksRepo::find_one(db, Any)
             .await?
-            .ok_or(utils::command_error("no pdx link db"))
+            .ok_or(DbError::NotFound)
     }
 
     pub fn update(&mut self, game: PdxGame, link: Option<String>) -> Result<(), String> {
@@ -153,3 +156,66 @@ impl PdxLinks {
         }
     }
 }
+
+pub struct PdxLinksRepo;
+
+impl Repository for PdxLinksRepo {
+    type Model = PdxLinks;
+    type Filter = Any;
+    const COLLECTION: &'static str = "pdx_links";
+}
+
+pub struct PdxFollows;
+
+impl Repository for PdxFollows {
+    type Model = PdxFollow;
+    type Filter = ByUser;
+    const COLLECTION: &'static str = "pdx_follows";
+}
+
+pub const COLLECTIONS: &[Collection] = &[
+    Collection {
+        name: PdxLinksRepo::COLLECTION,
+        model: check::<PdxLinks>,
+        unique: &[],
+        ttl: None,
+        forget: None,
+    },
+    Collection {
+        name: PdxFollows::COLLECTION,
+        model: check::<PdxFollow>,
+        unique: &[&["user_id"]],
+        ttl: None,
+        forget: Some(Hook {
+            label: "abonnements aux jeux Paradox",
+            forget: |db, user_id| PdxFollows::delete_many(db, ByUser(user_id)).boxed(),
+        }),
+    },
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pdx_links() {
+        let db = Db::memory();
+        assert!(PdxLinks::db_links(&db).await.is_err());
+
+        let links = PdxLinks::init();
+        PdxLinksRepo::insert(&db, &links).await.unwrap();
+        let mut stored = PdxLinks::db_links(&db).await.unwrap();
+        let old = stored.game_links(PdxGame::Hoi4).unwrap().latest;
+        stored
+            .update(PdxGame::Hoi4, Some("https://nouveau".to_owned()))
+            .unwrap();
+        let previous = PdxLinksRepo::replace(&db, &stored).await.unwrap().unwrap();
+        assert_eq!(previous.game_links(PdxGame::Hoi4).unwrap().latest, old);
+        assert_eq!(PdxLinksRepo::all(&db).await.unwrap().len(), 1);
+
+        let updated = PdxLinks::db_links(&db).await.unwrap();
+        let hoi4 = updated.game_links(PdxGame::Hoi4).unwrap();
+        assert_eq!(hoi4.latest, "https://nouveau");
+        assert_eq!(hoi4.previous, old);
+    }
+}
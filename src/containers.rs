@@ -1,7 +1,14 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use serenity::{gateway::ShardManager, model::prelude::*, prelude::*};
 
+use crate::answered::Answered;
+use crate::cooldown::Cooldowns;
+use crate::emojis::EmojiCache;
+use crate::mute::MuteCache;
+use crate::reactions::ReactionRule;
+
 pub struct ShardManagerContainer;
 
 impl TypeMapKey for ShardManagerContainer {
@@ -22,16 +29,38 @@ impl TypeMapKey for LogChanIdContainer {
     type Value = Arc<ChannelId>;
 }
 
-pub struct DatabaseUri(pub String);
+pub struct TempChanContainer;
 
-pub struct DatabaseUriContainer;
+impl TypeMapKey for TempChanContainer {
+    type Value = Arc<ChannelId>;
+}
 
-impl TypeMapKey for DatabaseUriContainer {
-    type Value = Arc<DatabaseUri>;
+pub struct ReactionRulesContainer;
+
+impl TypeMapKey for ReactionRulesContainer {
+    type Value = Arc<RwLock<HashMap<GuildId, Vec<ReactionRule>>>>;
 }
 
-pub struct TempChanContainer;
+pub struct CooldownsContainer;
 
-impl TypeMapKey for TempChanContainer {
-    type Value = Arc<ChannelId>;
+impl TypeMapKey for CooldownsContainer {
+    type Value = Arc<Mutex<Cooldowns>>;
+}
+
+pub struct MuteCacheContainer;
+
+impl TypeMapKey for MuteCacheContainer {
+    type Value = Arc<RwLock<MuteCache>>;
+}
+
+pub struct EmojiCacheContainer;
+
+impl TypeMapKey for EmojiCacheContainer {
+    type Value = Arc<RwLock<EmojiCache>>;
+}
+
+pub struct AnsweredContainer;
+
+impl TypeMapKey for AnsweredContainer {
+    type Value = Arc<Mutex<Answered>>;
 }
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+
+use bson::doc;
+use chrono::{DateTime, Duration, Utc};
+use futures::FutureExt;
+use mongodb::bson::oid::ObjectId;
+use rand::Rng;
+use serenity::model::prelude::{ChannelId, UserId};
+use serenity::prelude::Context;
+use tracing::error;
+
+use crate::collections::{check, Collection};
+use crate::db::{self, Db, DbError};
+use crate::forget::Hook;
+use crate::CooldownsContainer;
+
+pub const COLLECTION: &str = "reaction_cooldowns";
+
+// how often a reaction rule is allowed to fire
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Limits {
+    pub probability: f64,
+    pub channel_cooldown: u64,
+    pub user_cooldown: u64,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::ALWAYS
+    }
+}
+
+impl Limits {
+    pub const ALWAYS: Self = Self {
+        probability: 1.0,
+        channel_cooldown: 0,
+        user_cooldown: 0,
+    };
+
+    pub const fn new(probability: f64, channel_cooldown: u64, user_cooldown: u64) -> Self {
+        Self {
+            probability,
+            channel_cooldown,
+            user_cooldown,
+        }
+    }
+}
+
+impl std::fmt::Display for Limits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.0}% (cooldown salon : {}s, utilisateur : {}s)",
+            self.probability * 100.0_f64,
+            self.channel_cooldown,
+            self.user_cooldown
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
+pub enum Scope {
+    Channel,
+    User,
+}
+
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
+pub struct LastFired {
+    _id: ObjectId,
+    rule: String,
+    scope: Scope,
+    target_id: String,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    at: DateTime<Utc>,
+}
+
+// persisted cooldowns are kept that long, longer ones start over after a restart
+const KEPT_FOR: u64 = 7 * 24 * 60 * 60;
+
+fn seconds(cooldown: u64) -> Duration {
+    Duration::try_seconds(i64::try_from(cooldown).unwrap_or(i64::MAX))
+        .unwrap_or_else(Duration::max_value)
+}
+
+#[derive(Debug, Default)]
+pub struct Cooldowns {
+    persist: bool,
+    // when the rule last fired, and the cooldown it started
+    last_fired: HashMap<(String, Scope, String), (DateTime<Utc>, Duration)>,
+}
+
+impl Cooldowns {
+    pub fn new(persist: bool) -> Self {
+        Self {
+            persist,
+            last_fired: HashMap::new(),
+        }
+    }
+
+    fn is_cooling(&self, key: (String, Scope, String), cooldown: u64, now: DateTime<Utc>) -> bool {
+        cooldown > 0
+            && self
+                .last_fired
+                .get(&key)
+                .map_or(false, |(at, _)| now - *at < seconds(cooldown))
+    }
+
+    pub fn ready(
+        &self,
+        rule: &str,
+        channel_id: ChannelId,
+        user_id: UserId,
+        limits: Limits,
+        now: DateTime<Utc>,
+    ) -> bool {
+        !self.is_cooling(
+            (rule.to_owned(), Scope::Channel, channel_id.to_string()),
+            limits.channel_cooldown,
+            now,
+        ) && !self.is_cooling(
+            (rule.to_owned(), Scope::User, user_id.to_string()),
+            limits.user_cooldown,
+            now,
+        )
+    }
+
+    // only the rules with a cooldown are recorded, and the ended cooldowns are dropped
+    pub fn record(
+        &mut self,
+        rule: &str,
+        channel_id: ChannelId,
+        user_id: UserId,
+        limits: Limits,
+        now: DateTime<Utc>,
+    ) {
+        self.last_fired
+            .retain(|_, (at, cooldown)| now - *at < *cooldown);
+        for (scope, target_id, cooldown) in [
+            (
+                Scope::Channel,
+                channel_id.to_string(),
+                limits.channel_cooldown,
+            ),
+            (Scope::User, user_id.to_string(), limits.user_cooldown),
+        ] {
+            if cooldown > 0 {
+                self.last_fired.insert(
+                    (rule.to_owned(), scope, target_id),
+                    (now, seconds(cooldown)),
+                );
+            }
+        }
+    }
+
+    pub fn forget(&mut self, user_id: UserId) {
+        let user_id = user_id.to_string();
+        self.last_fired
+            .retain(|(_, scope, target_id), _| *scope != Scope::User || *target_id != user_id);
+    }
+}
+
+// checks cooldowns and probability of a rule, and records it as fired if allowed
+pub async fn allowed(
+    ctx: &Context,
+    db: &Db,
+    rule: &str,
+    channel_id: ChannelId,
+    user_id: UserId,
+    limits: Limits,
+) -> bool {
+    let now = Utc::now();
+    let data = ctx.data.read().await;
+    let Some(cooldowns) = data.get::<CooldownsContainer>() else {
+        error!("there was a problem getting the reaction cooldowns");
+        return true;
+    };
+
+    let persist = {
+        let mut cooldowns = cooldowns.lock().await;
+        if !cooldowns.ready(rule, channel_id, user_id, limits, now)
+            || !rand::thread_rng().gen_bool(limits.probability.clamp(0.0, 1.0))
+        {
+            return false;
+        }
+        cooldowns.record(rule, channel_id, user_id, limits, now);
+        cooldowns.persist
+    };
+
+    if persist {
+        for (scope, target_id, cooldown) in [
+            (
+                Scope::Channel,
+                channel_id.to_string(),
+                limits.channel_cooldown,
+            ),
+            (Scope::User, user_id.to_string(), limits.user_cooldown),
+        ] {
+            if cooldown == 0 {
+                continue;
+            }
+            if let Err(e) = save(db, rule, scope, target_id, now).await {
+                error!("error while saving reaction cooldown of {rule} : {e}");
+            }
+        }
+    }
+    true
+}
+
+async fn save(
+    db: &Db,
+    rule: &str,
+    scope: Scope,
+    target_id: String,
+    at: DateTime<Utc>,
+) -> Result<(), DbError> {
+    let query = doc! {"rule": rule, "scope": bson::to_bson(&scope)?, "target_id": target_id};
+    let update = doc! {"$set": {"at": bson::DateTime::from_chrono(at)}};
+    db::upsert_query(db, COLLECTION, query, update).await?;
+    Ok(())
+}
+
+// restores persisted cooldowns, used at startup when persistence is enabled
+pub async fn load(ctx: &Context, db: &Db) -> Result<(), DbError> {
+    let data = ctx.data.read().await;
+    let Some(cooldowns) = data.get::<CooldownsContainer>() else {
+        return Err(DbError::internal("no reaction cooldowns"));
+    };
+    let mut cooldowns = cooldowns.lock().await;
+    if !cooldowns.persist {
+        return Ok(());
+    }
+
+    let saved = db::get_objects::<LastFired>(db, COLLECTION, doc! {}).await?;
+    for last in saved {
+        cooldowns.last_fired.insert(
+            (last.rule, last.scope, last.target_id),
+            (last.at, seconds(KEPT_FOR)),
+        );
+    }
+    Ok(())
+}
+
+// drops the cached cooldowns of a user who asked to be forgotten
+pub async fn forget_cached(ctx: &Context, user_id: UserId) {
+    let data = ctx.data.read().await;
+    let Some(cooldowns) = data.get::<CooldownsContainer>() else {
+        error!("there was a problem getting the reaction cooldowns");
+        return;
+    };
+    cooldowns.lock().await.forget(user_id);
+}
+
+// only the cooldowns of the user, the channel ones aren't personal
+pub const COLLECTIONS: &[Collection] = &[Collection {
+    name: COLLECTION,
+    model: check::<LastFired>,
+    unique: &[&["rule", "scope", "target_id"]],
+    ttl: Some(("at", std::time::Duration::from_secs(KEPT_FOR))),
+    forget: Some(Hook {
+        label: "délais de réactions",
+        forget: |db, user_id| {
+            let filter = doc! {"scope": "User", "target_id": user_id.to_string()};
+            db::delete_multiple_query(db, COLLECTION, filter).boxed()
+        },
+    }),
+}];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cooldowns() {
+        let mut cooldowns = Cooldowns::new(false);
+        let now = Utc::now();
+        let chan = ChannelId::new(1);
+        let other_chan = ChannelId::new(2);
+        let user = UserId::new(10);
+        let other_user = UserId::new(11);
+        let limits = Limits::new(1.0, 60, 600);
+
+        assert!(cooldowns.ready("quoi", chan, user, limits, now));
+        cooldowns.record("quoi", chan, user, limits, now);
+
+        // same channel or same user is cooling
+        assert!(!cooldowns.ready("quoi", chan, other_user, limits, now));
+        assert!(!cooldowns.ready("quoi", other_chan, user, limits, now));
+        // other rules are not affected
+        assert!(cooldowns.ready("sus", chan, user, limits, now));
+        // no cooldown
+        assert!(cooldowns.ready("quoi", chan, user, Limits::ALWAYS, now));
+
+        let later = now + Duration::seconds(61);
+        assert!(cooldowns.ready("quoi", chan, other_user, limits, later));
+        assert!(!cooldowns.ready("quoi", other_chan, user, limits, later));
+        let much_later = now + Duration::seconds(601);
+        assert!(cooldowns.ready("quoi", other_chan, user, limits, much_later));
+
+        // the channel keeps cooling once the user is forgotten
+        cooldowns.forget(user);
+        assert!(cooldowns.ready("quoi", other_chan, user, limits, now));
+        assert!(!cooldowns.ready("quoi", chan, other_user, limits, now));
+
+        // rules without cooldown aren't kept, ended cooldowns are dropped
+        cooldowns.record("sus", chan, user, Limits::ALWAYS, now);
+        assert_eq!(cooldowns.last_fired.len(), 1);
+        cooldowns.record("sus", chan, user, Limits::ALWAYS, much_later);
+        assert!(cooldowns.last_fired.is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+use std::sync::OnceLock;
+
+use anyhow::anyhow;
+use chrono::{
+    DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use regex::Regex;
+
+use crate::text;
+
+// dates typed by users are in french time
+pub const TIMEZONE: Tz = chrono_tz::Europe::Paris;
+
+// time of the day when only a day is given
+const DEFAULT_HOUR: u32 = 9;
+
+pub fn now() -> DateTime<Tz> {
+    Utc::now().with_timezone(&TIMEZONE)
+}
+
+// "2h30", "dans 10 min", "à 18h", "demain 18h", "lundi 20h30", "25/12 18h", "2024-12-25 18:30"
+pub fn parse(input: &str, now: DateTime<Tz>) -> Result<DateTime<Utc>, anyhow::Error> {
+    let normalized = text::normalize(input.trim());
+    let s = normalized.strip_prefix("dans ").unwrap_or(&normalized);
+
+    // a bare "2h30" is a duration, "a 18h30" is a time of the day
+    let at = if let Some(rest) = s.strip_prefix("a ") {
+        parse_at(rest, now)?
+    } else if let Some(duration) = parse_duration(s)? {
+        now.checked_add_signed(duration).ok_or_else(invalid)?
+    } else {
+        parse_at(s, now)?
+    };
+
+    if at <= now {
+        return Err(anyhow!("cette date est déjà passée"));
+    }
+    Ok(at.with_timezone(&Utc))
+}
+
+fn invalid() -> anyhow::Error {
+    anyhow!("date invalide")
+}
+
+fn is_unit(unit: &str) -> bool {
+    matches!(
+        unit,
+        "s" | "sec"
+            | "secs"
+            | "seconde"
+            | "secondes"
+            | "m"
+            | "min"
+            | "mins"
+            | "minute"
+            | "minutes"
+            | "h"
+            | "heure"
+            | "heures"
+            | "j"
+            | "d"
+            | "jour"
+            | "jours"
+            | "sem"
+            | "semaine"
+            | "semaines"
+            | "w"
+    )
+}
+
+// None when the value doesn't fit in a duration
+fn unit_duration(value: i64, unit: &str) -> Option<Duration> {
+    match unit {
+        "s" | "sec" | "secs" | "seconde" | "secondes" => TimeDelta::try_seconds(value),
+        "m" | "min" | "mins" | "minute" | "minutes" => TimeDelta::try_minutes(value),
+        "h" | "heure" | "heures" => TimeDelta::try_hours(value),
+        "j" | "d" | "jour" | "jours" => TimeDelta::try_days(value),
+        _ => TimeDelta::try_weeks(value),
+    }
+}
+
+// "2h30", "1j 2h", "45 min", a number after hours is minutes,
+// Ok(None) when the text isn't a duration, an error when it's one too long
+pub fn parse_duration(s: &str) -> Result<Option<Duration>, anyhow::Error> {
+    static PART: OnceLock<Regex> = OnceLock::new();
+    let compact: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    let part = text::regex(&PART, r"(\d+)([a-z]*)");
+
+    let mut parts = Vec::new();
+    let mut end = 0;
+    let mut last_unit = "";
+    for caps in part.captures_iter(&compact) {
+        let Some(whole) = caps.get(0) else {
+            return Ok(None);
+        };
+        if whole.start() != end {
+            return Ok(None);
+        }
+        end = whole.end();
+        let unit = caps.get(2).map_or("", |m| m.as_str());
+        let unit = match (unit, last_unit) {
+            ("", "h" | "heure" | "heures") if end == compact.len() => "min",
+            (unit, _) if is_unit(unit) => unit,
+            _ => return Ok(None),
+        };
+        parts.push((caps.get(1).map_or("", |m| m.as_str()), unit));
+        last_unit = unit;
+    }
+    if end == 0 || end != compact.len() {
+        return Ok(None);
+    }
+
+    let mut total = Duration::zero();
+    for (value, unit) in parts {
+        let value: i64 = value.parse().map_err(|_| invalid())?;
+        let duration = unit_duration(value, unit).ok_or_else(invalid)?;
+        total = total.checked_add(&duration).ok_or_else(invalid)?;
+    }
+    Ok((total > Duration::zero()).then_some(total))
+}
+
+fn parse_time(s: &str) -> Option<NaiveTime> {
+    static TIME: OnceLock<Regex> = OnceLock::new();
+    match s {
+        "midi" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "minuit" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => {}
+    }
+    let caps = text::regex(&TIME, r"^(\d{1,2})(?:h|:)(\d{2})?$").captures(s)?;
+    let hour = caps[1].parse().ok()?;
+    let minute = caps.get(2).map_or(Some(0), |m| m.as_str().parse().ok())?;
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+fn parse_weekday(s: &str) -> Option<Weekday> {
+    match s {
+        "lundi" => Some(Weekday::Mon),
+        "mardi" => Some(Weekday::Tue),
+        "mercredi" => Some(Weekday::Wed),
+        "jeudi" => Some(Weekday::Thu),
+        "vendredi" => Some(Weekday::Fri),
+        "samedi" => Some(Weekday::Sat),
+        "dimanche" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+enum DaySpec {
+    Date(NaiveDate),
+    // day and month, the year is the next one where it's not passed
+    DayMonth(u32, u32),
+    Weekday(Weekday),
+}
+
+fn parse_day(s: &str, today: NaiveDate) -> Option<DaySpec> {
+    static DATE: OnceLock<Regex> = OnceLock::new();
+    static ISO: OnceLock<Regex> = OnceLock::new();
+    match s {
+        "aujourd'hui" | "aujourdhui" => return Some(DaySpec::Date(today)),
+        "demain" => return today.succ_opt().map(DaySpec::Date),
+        "apres-demain" => return today.succ_opt()?.succ_opt().map(DaySpec::Date),
+        _ => {}
+    }
+    if let Some(weekday) = parse_weekday(s) {
+        return Some(DaySpec::Weekday(weekday));
+    }
+    if let Some(caps) = text::regex(&ISO, r"^(\d{4})-(\d{2})-(\d{2})$").captures(s) {
+        let date = NaiveDate::from_ymd_opt(
+            caps[1].parse().ok()?,
+            caps[2].parse().ok()?,
+            caps[3].parse().ok()?,
+        )?;
+        return Some(DaySpec::Date(date));
+    }
+    let caps = text::regex(&DATE, r"^(\d{1,2})/(\d{1,2})(?:/(\d{2}|\d{4}))?$").captures(s)?;
+    let day = caps[1].parse().ok()?;
+    let month = caps[2].parse().ok()?;
+    match caps.get(3) {
+        Some(year) => {
+            let year: i32 = year.as_str().parse().ok()?;
+            let year = if year < 100 { 2000 + year } else { year };
+            NaiveDate::from_ymd_opt(year, month, day).map(DaySpec::Date)
+        }
+        None => Some(DaySpec::DayMonth(day, month)),
+    }
+}
+
+// a day and/or a time of the day, the next matching moment
+fn parse_at(s: &str, now: DateTime<Tz>) -> Result<DateTime<Tz>, anyhow::Error> {
+    let unknown = || anyhow!("format de date non reconnu : \"{s}\"");
+    let words: Vec<&str> = s
+        .split_whitespace()
+        .filter(|word| !["a", "le"].contains(word))
+        .collect();
+    let today = now.date_naive();
+
+    let (day, time) = match words.as_slice() {
+        [word] => match parse_time(word) {
+            Some(time) => (None, Some(time)),
+            None => (Some(parse_day(word, today).ok_or_else(unknown)?), None),
+        },
+        [day, time] => (
+            Some(parse_day(day, today).ok_or_else(unknown)?),
+            Some(parse_time(time).ok_or_else(unknown)?),
+        ),
+        _ => return Err(unknown()),
+    };
+
+    let time =
+        time.unwrap_or_else(|| NaiveTime::from_hms_opt(DEFAULT_HOUR, 0, 0).unwrap_or_default());
+    let local = |date: NaiveDate| {
+        TIMEZONE
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+            .ok_or_else(|| anyhow!("cette heure n'existe pas à cause du changement d'heure"))
+    };
+    let date = match day {
+        None if local(today)? > now => today,
+        None => today.succ_opt().ok_or_else(unknown)?,
+        Some(DaySpec::Date(date)) => date,
+        Some(DaySpec::Weekday(weekday)) => {
+            let ahead =
+                (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+            let date = today + Duration::days(i64::from(ahead));
+            if local(date)? > now {
+                date
+            } else {
+                date + Duration::weeks(1)
+            }
+        }
+        Some(DaySpec::DayMonth(day, month)) => {
+            let this_year =
+                NaiveDate::from_ymd_opt(today.year(), month, day).ok_or_else(unknown)?;
+            if local(this_year)? > now {
+                this_year
+            } else {
+                NaiveDate::from_ymd_opt(today.year() + 1, month, day).ok_or_else(unknown)?
+            }
+        }
+    };
+    local(date)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // wednesday 15 may 2024, 14:00 in Paris
+    fn now() -> DateTime<Tz> {
+        TIMEZONE.with_ymd_and_hms(2024, 5, 15, 14, 0, 0).unwrap()
+    }
+
+    fn at(month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        TIMEZONE
+            .with_ymd_and_hms(2024, month, day, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        let parse_duration = |s| parse_duration(s).ok().flatten();
+        assert_eq!(parse_duration("2h30"), Some(Duration::minutes(150)));
+        assert_eq!(parse_duration("1j 2h"), Some(Duration::hours(26)));
+        assert_eq!(parse_duration("45 min"), Some(Duration::minutes(45)));
+        assert_eq!(parse_duration("2 semaines"), Some(Duration::weeks(2)));
+        assert_eq!(parse_duration("30"), None);
+        assert_eq!(parse_duration("2h30x"), None);
+        assert_eq!(parse_duration("demain"), None);
+        assert_eq!(parse_duration("0min"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        let parse = |s| parse(s, now()).ok();
+        assert_eq!(parse("2h30"), Some(at(5, 15, 16, 30)));
+        assert_eq!(parse("dans 10 min"), Some(at(5, 15, 14, 10)));
+        assert_eq!(parse("à 18h"), Some(at(5, 15, 18, 0)));
+        assert_eq!(parse("a 9h"), Some(at(5, 16, 9, 0)));
+        assert_eq!(parse("18:30"), Some(at(5, 15, 18, 30)));
+        assert_eq!(parse("demain 18h"), Some(at(5, 16, 18, 0)));
+        assert_eq!(parse("Demain à 18h15"), Some(at(5, 16, 18, 15)));
+        assert_eq!(parse("demain"), Some(at(5, 16, 9, 0)));
+        assert_eq!(parse("après-demain midi"), Some(at(5, 17, 12, 0)));
+        assert_eq!(parse("vendredi 20h"), Some(at(5, 17, 20, 0)));
+        assert_eq!(parse("mercredi 20h"), Some(at(5, 15, 20, 0)));
+        assert_eq!(parse("mercredi 10h"), Some(at(5, 22, 10, 0)));
+        assert_eq!(parse("25/12 18h"), Some(at(12, 25, 18, 0)));
+        assert_eq!(parse("2024-06-01 8h"), Some(at(6, 1, 8, 0)));
+        assert_eq!(
+            parse("1/1"),
+            TIMEZONE
+                .with_ymd_and_hms(2025, 1, 1, 9, 0, 0)
+                .single()
+                .map(|d| d.with_timezone(&Utc))
+        );
+
+        assert_eq!(parse("hier"), None);
+        assert_eq!(parse("à 25h"), None);
+        assert_eq!(parse("01/01/2020"), None);
+        assert_eq!(parse("demain 18h bonjour"), None);
+        // too far to be a date, not a panic
+        assert_eq!(parse("99999999999999h"), None);
+        assert_eq!(parse("99999999999999999999 semaines"), None);
+        assert!(parse_duration("99999999999999h").is_err());
+    }
+}
@@ -1,20 +1,18 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use bson::Bson;
 use bson::Document;
 use mongodb::bson::doc;
-use mongodb::bson::to_document;
-use mongodb::options::UpdateModifications;
-use mongodb::results::DeleteResult;
-use mongodb::results::UpdateResult;
-use mongodb::{error::Error, options::ClientOptions, Client, Collection};
-use serenity::futures::TryStreamExt;
-use serenity::prelude::Context;
+use mongodb::bson::{from_document, to_document};
+use mongodb::error::{ErrorKind, WriteFailure};
 
-use crate::DatabaseUriContainer;
+use crate::storage::{Memory, Mongo, Sqlite, Storage};
 
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct User {
     _id: mongodb::bson::oid::ObjectId,
-    user_id: String,
+    pub user_id: String,
 }
 
 impl User {
@@ -29,7 +27,7 @@ impl User {
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct Chan {
     _id: mongodb::bson::oid::ObjectId,
-    channel_id: String,
+    pub channel_id: String,
 }
 
 impl Chan {
@@ -44,7 +42,7 @@ impl Chan {
 #[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub struct Guild {
     _id: mongodb::bson::oid::ObjectId,
-    guild_id: String,
+    pub guild_id: String,
 }
 
 impl Guild {
@@ -56,254 +54,394 @@ impl Guild {
     }
 }
 
-pub fn mongodb_error<T: Into<String>>(message: T) -> Error {
-    Error::from(std::io::Error::new(
-        std::io::ErrorKind::Other,
-        message.into(),
-    ))
+// what a document stored in a collection has to implement
+pub trait Model:
+    core::fmt::Debug + serde::de::DeserializeOwned + serde::Serialize + Unpin + Send + Sync
+{
 }
 
-async fn get_client(ctx: &Context) -> Result<Client, Error> {
-    let data = ctx.data.read().await;
-    if let Some(db) = data.get::<DatabaseUriContainer>() {
-        let options = ClientOptions::parse(&db.0).await?; // FIXME: very slow
-        Client::with_options(options)
-    } else {
-        Err(mongodb_error("no db uri"))
+impl<T> Model for T where
+    T: core::fmt::Debug + serde::de::DeserializeOwned + serde::Serialize + Unpin + Send + Sync
+{
+}
+
+// errors of every storage, commands reply with `DbError::reply`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DbError {
+    NotFound,
+    AlreadyExists,
+    Connection(String),
+    Serialization(String),
+    // bad query, poisoned lock, missing cache...
+    Internal(String),
+}
+
+// code of mongodb duplicate key errors
+const DUPLICATE_KEY: i32 = 11000;
+
+// numbered inserts read the next number then insert, when another insert took it
+// meanwhile the unique index rejects the document and it is tried again
+pub const INSERT_ATTEMPTS: u32 = 5;
+
+impl DbError {
+    pub fn internal<T: Into<String>>(message: T) -> Self {
+        Self::Internal(message.into())
+    }
+
+    // message shown to users, the details are only logged
+    pub const fn reply(&self) -> &'static str {
+        match self {
+            Self::NotFound => "Ce que vous cherchez n'existe pas (ou plus)",
+            Self::AlreadyExists => "Cela existe déjà",
+            Self::Connection(_) => "La base de données est injoignable, réessayez plus tard",
+            Self::Serialization(_) => "Les données enregistrées sont illisibles, prévenez un admin",
+            Self::Internal(_) => "Erreur interne du bot, prévenez un admin",
+        }
+    }
+}
+
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "objet introuvable"),
+            Self::AlreadyExists => write!(f, "l'objet existe déjà"),
+            Self::Connection(e) => write!(f, "erreur de connexion à la base de données : {e}"),
+            Self::Serialization(e) => write!(f, "erreur de sérialisation : {e}"),
+            Self::Internal(e) => write!(f, "erreur interne : {e}"),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<mongodb::error::Error> for DbError {
+    fn from(e: mongodb::error::Error) -> Self {
+        match *e.kind {
+            ErrorKind::Write(WriteFailure::WriteError(ref write))
+                if write.code == DUPLICATE_KEY =>
+            {
+                Self::AlreadyExists
+            }
+            ErrorKind::Command(ref command) if command.code == DUPLICATE_KEY => Self::AlreadyExists,
+            ErrorKind::BsonDeserialization(_) | ErrorKind::BsonSerialization(_) => {
+                Self::Serialization(e.to_string())
+            }
+            _ => Self::Connection(e.to_string()),
+        }
+    }
+}
+
+impl From<bson::ser::Error> for DbError {
+    fn from(e: bson::ser::Error) -> Self {
+        Self::Serialization(e.to_string())
+    }
+}
+
+impl From<bson::de::Error> for DbError {
+    fn from(e: bson::de::Error) -> Self {
+        Self::Serialization(e.to_string())
     }
 }
 
-pub async fn get_coll<
-    'a,
-    T: core::fmt::Debug + serde::Deserialize<'a> + serde::Serialize + Send + Sync,
->(
-    ctx: &Context,
+impl From<rusqlite::Error> for DbError {
+    fn from(e: rusqlite::Error) -> Self {
+        Self::Connection(format!("sqlite : {e}"))
+    }
+}
+
+// storage shared by the whole bot, cheap to clone
+#[derive(Debug, Clone)]
+pub struct Db {
+    storage: Arc<dyn Storage>,
+}
+
+impl Db {
+    pub fn new(storage: impl Storage + 'static) -> Self {
+        Self {
+            storage: Arc::new(storage),
+        }
+    }
+
+    // one connection pool for the whole bot
+    pub async fn connect(uri: &str) -> Result<Self, DbError> {
+        Ok(Self::new(Mongo::connect(uri).await?))
+    }
+
+    // single file database, for hosts without mongodb
+    pub fn sqlite(path: &str) -> Result<Self, DbError> {
+        Ok(Self::new(Sqlite::open(path)?))
+    }
+
+    // empty database lost when the bot stops
+    pub fn memory() -> Self {
+        Self::new(Memory::default())
+    }
+}
+
+fn from_documents<T: Model>(documents: Vec<Document>) -> Result<Vec<T>, DbError> {
+    documents
+        .into_iter()
+        .map(|d| from_document(d).map_err(DbError::from))
+        .collect()
+}
+
+// matches the stored documents with the same fields as the object, _id aside
+fn object_filter<T: Model>(object: &T) -> Result<Document, DbError> {
+    let mut doc_filter = to_document(object)?;
+    doc_filter.remove("_id");
+    Ok(doc_filter)
+}
+
+async fn find_stored<T: Model>(
+    db: &Db,
     collection: &str,
-) -> Result<Collection<T>, Error> {
-    Ok(get_client(ctx)
-        .await?
-        .database("teamy")
-        .collection(collection))
-}
-
-pub async fn get_object<
-    T: core::fmt::Debug
-        + serde::de::DeserializeOwned
-        + serde::Serialize
-        + std::marker::Unpin
-        + std::marker::Send
-        + std::marker::Sync,
->(
-    ctx: &Context,
+    object: &T,
+) -> Result<Option<Document>, DbError> {
+    db.storage
+        .find_one(collection, object_filter(object)?)
+        .await
+}
+
+pub async fn get_object<T: Model>(
+    db: &Db,
     collection: &str,
     object: &T,
-) -> Result<Option<T>, Error> {
-    let coll: Collection<T> = get_coll(ctx, collection).await?;
-    let mut doc_filter = to_document(&object)?;
-    doc_filter.remove("_id");
-    let o = coll.find_one(doc_filter).await?;
-    Ok(o)
-}
-
-pub async fn get_objects<
-    T: core::fmt::Debug
-        + serde::de::DeserializeOwned
-        + serde::Serialize
-        + std::marker::Unpin
-        + std::marker::Send
-        + std::marker::Sync,
->(
-    ctx: &Context,
+) -> Result<Option<T>, DbError> {
+    match find_stored(db, collection, object).await? {
+        Some(document) => Ok(Some(from_document(document)?)),
+        None => Ok(None),
+    }
+}
+
+pub async fn get_objects<T: Model>(
+    db: &Db,
     collection: &str,
     filter: impl Into<Option<Document>>,
-) -> Result<Vec<T>, Error> {
+) -> Result<Vec<T>, DbError> {
     if let Some(document) = filter.into() {
-        get_coll::<T>(ctx, collection)
-            .await?
-            .find(document)
-            .await?
-            .try_collect::<Vec<T>>()
-            .await
+        from_documents(db.storage.find(collection, document).await?)
     } else {
-        Err(mongodb_error("get_objects: can't filter into document"))
+        Err(DbError::internal("get_objects: can't filter into document"))
     }
 }
 
-pub async fn is_object_in_coll<
-    T: core::fmt::Debug
-        + serde::de::DeserializeOwned
-        + serde::Serialize
-        + std::marker::Unpin
-        + std::marker::Send
-        + std::marker::Sync,
->(
-    ctx: &Context,
+pub async fn is_object_in_coll<T: Model>(
+    db: &Db,
     collection: &str,
     object: &T,
-) -> Result<bool, Error> {
-    match get_object(ctx, collection, object).await? {
-        Some(_) => Ok(true),
-        None => Ok(false),
-    }
+) -> Result<bool, DbError> {
+    Ok(find_stored(db, collection, object).await?.is_some())
 }
 
-pub async fn insert<
-    T: core::fmt::Debug
-        + serde::de::DeserializeOwned
-        + serde::Serialize
-        + std::marker::Unpin
-        + std::marker::Send
-        + std::marker::Sync,
->(
-    ctx: &Context,
+// atomic, the object isn't inserted if an identical one exists
+pub async fn insert<T: Model>(db: &Db, collection: &str, object: &T) -> Result<Bson, DbError> {
+    db.storage
+        .insert_if_absent(collection, object_filter(object)?, to_document(object)?)
+        .await?
+        .ok_or(DbError::AlreadyExists)
+}
+
+pub async fn update<T: Model>(
+    db: &Db,
     collection: &str,
     object: &T,
-) -> Result<Bson, Error> {
-    let coll: Collection<T> = get_coll(ctx, collection).await?;
-    if is_object_in_coll(ctx, collection, object).await? {
-        Err(mongodb_error("l'objet à insérer existe déjà"))
+    update: &bson::Document,
+) -> Result<(), DbError> {
+    let updated = db
+        .storage
+        .update_one(collection, object_filter(object)?, update.clone(), false)
+        .await?;
+    if updated == 0 {
+        Err(DbError::NotFound)
     } else {
-        Ok(coll.insert_one(object).await?.inserted_id)
+        Ok(())
     }
 }
 
-pub async fn update<
-    T: core::fmt::Debug
-        + serde::de::DeserializeOwned
-        + serde::Serialize
-        + std::marker::Unpin
-        + std::marker::Send
-        + std::marker::Sync,
->(
-    ctx: &Context,
+pub async fn update_query(
+    db: &Db,
     collection: &str,
-    object: &T,
-    update: &bson::Document,
-) -> Result<(), Error> {
-    let coll: Collection<T> = get_coll(ctx, collection).await?;
-    if let Ok(o) = get_object(ctx, collection, object).await {
-        match o {
-            Some(res) => {
-                let _: UpdateResult = coll
-                    .update_one(
-                        doc! {"_id": to_document(&res)?.get("_id")},
-                        (*update).clone(),
-                    )
-                    .await?;
-                Ok(())
-            }
-            None => Err(mongodb_error("l'objet à modifier n'existe pas")),
-        }
-    } else {
-        Err(mongodb_error(format!(
-            "erreur pour accéder à l'objet : {object:?}"
-        )))
+    query: Document,
+    update: Document,
+) -> Result<u64, DbError> {
+    db.storage
+        .update_one(collection, query, update, false)
+        .await
+}
+
+// updates the matching documents one by one, so not atomic as a whole,
+// returns the number of updated documents
+pub async fn update_multiple_query(
+    db: &Db,
+    collection: &str,
+    query: Document,
+    update: Document,
+) -> Result<u64, DbError> {
+    let mut updated = 0;
+    for document in db.storage.find(collection, query).await? {
+        let filter = doc! {"_id": document.get("_id")};
+        updated += db
+            .storage
+            .update_one(collection, filter, update.clone(), false)
+            .await?;
     }
+    Ok(updated)
 }
 
-pub async fn update_query<
-    T: core::fmt::Debug
-        + serde::de::DeserializeOwned
-        + serde::Serialize
-        + std::marker::Unpin
-        + std::marker::Send
-        + std::marker::Sync,
->(
-    ctx: &Context,
+pub async fn upsert_query(
+    db: &Db,
     collection: &str,
     query: Document,
-    update: impl Into<UpdateModifications>,
-) -> Result<UpdateResult, Error> {
-    let coll: Collection<T> = get_coll(ctx, collection).await?;
-    coll.update_one(query, update).await
-}
-
-pub async fn delete<
-    T: core::fmt::Debug
-        + serde::de::DeserializeOwned
-        + serde::Serialize
-        + std::marker::Unpin
-        + std::marker::Send
-        + std::marker::Sync,
->(
-    ctx: &Context,
+    update: Document,
+) -> Result<u64, DbError> {
+    db.storage.update_one(collection, query, update, true).await
+}
+
+pub async fn delete<T: Model>(db: &Db, collection: &str, object: &T) -> Result<(), DbError> {
+    let deleted = db
+        .storage
+        .delete_one(collection, object_filter(object)?)
+        .await?;
+    if deleted == 0 {
+        Err(DbError::NotFound)
+    } else {
+        Ok(())
+    }
+}
+
+// replaces the stored object with the same _id in one operation, inserts it if there is none,
+// returns the previous version
+pub async fn replace<T: Model>(
+    db: &Db,
     collection: &str,
     object: &T,
-) -> Result<(), Error> {
-    let coll: Collection<T> = get_coll(ctx, collection).await?;
-    if let Ok(o) = get_object(ctx, collection, object).await {
-        match o {
-            Some(res) => {
-                let _: DeleteResult = coll
-                    .delete_one(doc! {"_id": to_document(&res)?.get("_id")})
-                    .await?;
-                Ok(())
-            }
-            None => Err(mongodb_error("l'objet à supprimer n'existe pas")),
-        }
-    } else {
-        Err(mongodb_error(format!(
-            "erreur pour accéder à l'objet : {object:?}"
-        )))
+) -> Result<Option<T>, DbError> {
+    let document = to_document(object)?;
+    let filter = doc! {"_id": document.get("_id")};
+    match db
+        .storage
+        .find_one_and_replace(collection, filter, document, true)
+        .await?
+    {
+        Some(previous) => Ok(Some(from_document(previous)?)),
+        None => Ok(None),
     }
 }
 
-pub async fn find_filter<
-    T: core::fmt::Debug
-        + serde::de::DeserializeOwned
-        + serde::Serialize
-        + std::marker::Unpin
-        + std::marker::Send
-        + std::marker::Sync,
->(
-    ctx: &Context,
+pub async fn create_unique_index(
+    db: &Db,
+    collection: &str,
+    fields: &[&str],
+) -> Result<(), DbError> {
+    db.storage.create_unique_index(collection, fields).await
+}
+
+pub async fn create_ttl_index(
+    db: &Db,
+    collection: &str,
+    field: &str,
+    expire_after: Duration,
+) -> Result<(), DbError> {
+    db.storage
+        .create_ttl_index(collection, field, expire_after)
+        .await
+}
+
+pub async fn find_filter<T: Model>(
+    db: &Db,
     collection: &str,
     filter: impl Into<Option<Document>>,
-) -> Result<Option<T>, Error> {
+) -> Result<Option<T>, DbError> {
     if let Some(document) = filter.into() {
-        get_coll::<T>(ctx, collection)
-            .await?
-            .find_one(document)
-            .await
+        match db.storage.find_one(collection, document).await? {
+            Some(document) => Ok(Some(from_document(document)?)),
+            None => Ok(None),
+        }
     } else {
-        Err(mongodb_error("find_filter: can't filter into document"))
+        Err(DbError::internal("find_filter: can't filter into document"))
     }
 }
 
-pub async fn delete_query<
-    T: core::fmt::Debug
-        + serde::de::DeserializeOwned
-        + serde::Serialize
-        + std::marker::Unpin
-        + std::marker::Send
-        + std::marker::Sync,
->(
-    ctx: &Context,
+pub async fn delete_query(db: &Db, collection: &str, query: Document) -> Result<(), DbError> {
+    db.storage.delete_one(collection, query).await?;
+    Ok(())
+}
+
+// returns the number of deleted documents
+pub async fn delete_multiple_query(
+    db: &Db,
     collection: &str,
     query: Document,
-) -> Result<(), Error> {
-    get_coll::<T>(ctx, collection)
-        .await?
-        .delete_one(query)
-        .await?;
-    Ok(())
+) -> Result<u64, DbError> {
+    db.storage.delete_many(collection, query).await
 }
 
-pub async fn delete_multiple_query<
-    T: core::fmt::Debug
-        + serde::de::DeserializeOwned
-        + serde::Serialize
-        + std::marker::Unpin
-        + std::marker::Send
-        + std::marker::Sync,
->(
-    ctx: &Context,
+// documents as stored, for code that must not depend on the current models
+pub async fn raw_documents(
+    db: &Db,
+    collection: &str,
+    filter: Document,
+) -> Result<Vec<Document>, DbError> {
+    db.storage.find(collection, filter).await
+}
+
+// inserts the document as is, for data that doesn't come from a model
+pub async fn insert_raw(db: &Db, collection: &str, document: Document) -> Result<Bson, DbError> {
+    db.storage.insert_one(collection, document).await
+}
+
+// inserts the document only if none matches the query, in one operation,
+// returns false if one already matched
+pub async fn insert_raw_if_absent(
+    db: &Db,
     collection: &str,
     query: Document,
-) -> Result<(), Error> {
-    get_coll::<T>(ctx, collection)
-        .await?
-        .delete_many(query)
+    document: Document,
+) -> Result<bool, DbError> {
+    match db
+        .storage
+        .insert_if_absent(collection, query, document)
+        .await
+    {
+        Ok(id) => Ok(id.is_some()),
+        // another insert with the same unique fields won the race
+        Err(DbError::AlreadyExists) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// replaces the stored document with the same _id
+pub async fn replace_raw(db: &Db, collection: &str, document: Document) -> Result<(), DbError> {
+    let Some(id) = document.get("_id").cloned() else {
+        return Err(DbError::internal("replace_raw: document without _id"));
+    };
+    db.storage
+        .update_one(collection, doc! {"_id": id}, document, false)
         .await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_errors() {
+        let db = Db::memory();
+        let user = User::builder("10".to_owned());
+        insert(&db, "mute users", &user).await.unwrap();
+        assert_eq!(
+            insert(&db, "mute users", &user).await,
+            Err(DbError::AlreadyExists)
+        );
+        delete(&db, "mute users", &user).await.unwrap();
+        assert_eq!(
+            delete(&db, "mute users", &user).await,
+            Err(DbError::NotFound)
+        );
+        assert_eq!(
+            DbError::from(bson::de::Error::EndOfStream),
+            DbError::Serialization(bson::de::Error::EndOfStream.to_string())
+        );
+    }
+}
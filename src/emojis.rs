@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use serenity::model::prelude::{Emoji, GuildId};
+use serenity::prelude::Context;
+use tracing::error;
+
+use crate::text;
+use crate::EmojiCacheContainer;
+
+// maximum edit distance for a name to still be considered a match
+const MAX_DISTANCE: usize = 2;
+
+// guild emojis, filled at startup and kept up to date by gateway events
+#[derive(Debug, Default)]
+pub struct EmojiCache {
+    guilds: HashMap<GuildId, Vec<Emoji>>,
+}
+
+impl EmojiCache {
+    pub fn set(&mut self, guild_id: GuildId, emojis: Vec<Emoji>) {
+        self.guilds.insert(guild_id, emojis);
+    }
+
+    pub fn contains(&self, guild_id: GuildId) -> bool {
+        self.guilds.contains_key(&guild_id)
+    }
+
+    pub fn find(&self, guild_id: GuildId, name: &str) -> Option<&Emoji> {
+        let emojis = self.guilds.get(&guild_id)?;
+        best_match(emojis.iter().map(|e| e.name.as_str()), name).map(|i| &emojis[i])
+    }
+}
+
+// index of the closest name: exact, then normalized, then containing, then by edit distance
+pub fn best_match<'a>(names: impl Iterator<Item = &'a str> + Clone, query: &str) -> Option<usize> {
+    if let Some(i) = names.clone().position(|name| name == query) {
+        return Some(i);
+    }
+
+    let query = text::normalize(query);
+    let normalized: Vec<String> = names.map(text::normalize).collect();
+    if let Some(i) = normalized.iter().position(|name| *name == query) {
+        return Some(i);
+    }
+    if let Some(i) = normalized.iter().position(|name| name.contains(&query)) {
+        return Some(i);
+    }
+    normalized
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (i, levenshtein(name, &query)))
+        .filter(|(_, distance)| *distance <= MAX_DISTANCE)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(i, _)| i)
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            current.push(
+                (previous[j] + cost)
+                    .min(previous[j + 1] + 1)
+                    .min(current[j] + 1),
+            );
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+pub async fn set_guild(ctx: &Context, guild_id: GuildId, emojis: Vec<Emoji>) {
+    let data = ctx.data.read().await;
+    if let Some(cache) = data.get::<EmojiCacheContainer>() {
+        cache.write().await.set(guild_id, emojis);
+    } else {
+        error!("there was a problem getting the emoji cache");
+    }
+}
+
+pub async fn fetch_guild(ctx: &Context, guild_id: GuildId) -> Result<(), serenity::Error> {
+    let emojis = guild_id.emojis(&ctx.http).await?;
+    set_guild(ctx, guild_id, emojis).await;
+    Ok(())
+}
+
+async fn is_cached(ctx: &Context, guild_id: GuildId) -> bool {
+    let data = ctx.data.read().await;
+    let Some(cache) = data.get::<EmojiCacheContainer>() else {
+        return false;
+    };
+    let cache = cache.read().await;
+    cache.contains(guild_id)
+}
+
+pub async fn find(ctx: &Context, guild_id: GuildId, name: &str) -> Option<Emoji> {
+    // guild not seen yet, fill it once
+    if !is_cached(ctx, guild_id).await {
+        if let Err(e) = fetch_guild(ctx, guild_id).await {
+            error!("error while getting emojis of guild {guild_id} : {e}");
+            return None;
+        }
+    }
+    let data = ctx.data.read().await;
+    let cache = data.get::<EmojiCacheContainer>()?.read().await;
+    cache.find(guild_id, name).cloned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_best_match() {
+        let names = ["saucisse", "afungus", "Bedge", "pepe_laugh"];
+        let find = |query| best_match(names.iter().copied(), query);
+
+        assert_eq!(find("saucisse"), Some(0));
+        assert_eq!(find("bedge"), Some(2));
+        assert_eq!(find("laugh"), Some(3));
+        assert_eq!(find("afungos"), Some(1));
+        assert_eq!(find("sosis"), None);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+    }
+}
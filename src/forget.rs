@@ -0,0 +1,165 @@
+use futures::future::BoxFuture;
+use serenity::all::{
+    ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton, CreateInteractionResponse,
+    CreateInteractionResponseMessage, UserId,
+};
+use serenity::prelude::Context;
+use tracing::{error, info};
+
+use crate::collections;
+use crate::db::{Db, DbError};
+use crate::{cooldown, mute};
+
+// custom ids of the confirmation buttons are "oublie-moi:<user id>:<action>"
+pub const BUTTON_PREFIX: &str = "oublie-moi";
+const CONFIRM: &str = "confirmer";
+const CANCEL: &str = "annuler";
+
+pub type Cleanup = for<'a> fn(&'a Db, UserId) -> BoxFuture<'a, Result<u64, DbError>>;
+
+// what a module deletes when a user asks to be forgotten,
+// returns the number of deleted or anonymized documents
+pub struct Hook {
+    pub label: &'static str,
+    pub forget: Cleanup,
+}
+
+// the hooks of the collections holding user data
+pub fn hooks() -> impl Iterator<Item = (&'static str, &'static Hook)> {
+    collections::all().filter_map(|c| Some((c.name, c.forget.as_ref()?)))
+}
+
+// runs every hook, the summary lists what each one removed
+pub async fn forget(db: &Db, user_id: UserId) -> Result<Vec<(&'static str, u64)>, DbError> {
+    let mut summary = Vec::new();
+    for (collection, hook) in hooks() {
+        let count = (hook.forget)(db, user_id).await?;
+        info!("{count} document(s) of user {user_id} removed from {collection}");
+        summary.push((hook.label, count));
+    }
+    Ok(summary)
+}
+
+pub fn summary_text(summary: &[(&str, u64)]) -> String {
+    let lines: Vec<String> = summary
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(label, count)| format!("- {label} : {count}"))
+        .collect();
+    if lines.is_empty() {
+        String::from("Le bot n'avait aucune donnée sur vous")
+    } else {
+        format!("Vos données ont été supprimées :\n{}", lines.join("\n"))
+    }
+}
+
+pub fn buttons(user_id: UserId) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{BUTTON_PREFIX}:{user_id}:{CONFIRM}"))
+            .label("Tout supprimer")
+            .style(ButtonStyle::Danger),
+        CreateButton::new(format!("{BUTTON_PREFIX}:{user_id}:{CANCEL}"))
+            .label("Annuler")
+            .style(ButtonStyle::Secondary),
+    ])]
+}
+
+fn parse_custom_id(custom_id: &str) -> Option<(UserId, bool)> {
+    let mut parts = custom_id.split(':');
+    if parts.next()? != BUTTON_PREFIX {
+        return None;
+    }
+    let user_id = parts.next()?.parse().ok()?;
+    let confirmed = match parts.next()? {
+        CONFIRM => true,
+        CANCEL => false,
+        _ => return None,
+    };
+    parts.next().is_none().then_some((user_id, confirmed))
+}
+
+// only the user who asked can confirm, the buttons are replaced by the summary
+pub async fn confirm(
+    ctx: &Context,
+    db: &Db,
+    interaction: &ComponentInteraction,
+) -> Result<(), serenity::Error> {
+    let Some((user_id, confirmed)) = parse_custom_id(&interaction.data.custom_id) else {
+        return Ok(());
+    };
+    if interaction.user.id != user_id {
+        return Ok(());
+    }
+
+    let content = if confirmed {
+        match forget(db, user_id).await {
+            Ok(summary) => {
+                if let Err(e) = mute::load(ctx, db).await {
+                    error!("error while reloading muted users, chans and guilds : {e}");
+                }
+                cooldown::forget_cached(ctx, user_id).await;
+                summary_text(&summary)
+            }
+            Err(e) => {
+                error!("error while deleting the data of user {user_id} : {e}");
+                format!("{}, rien n'est perdu en réessayant", e.reply())
+            }
+        }
+    } else {
+        String::from("Rien n'a été supprimé")
+    };
+    let message = CreateInteractionResponseMessage::new()
+        .content(content)
+        .components(Vec::new());
+    interaction
+        .create_response(&ctx.http, CreateInteractionResponse::UpdateMessage(message))
+        .await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::{self, User};
+    use crate::polls;
+    use crate::repository::{MuteUsers, Repository};
+
+    #[test]
+    fn test_parse_custom_id() {
+        assert_eq!(
+            parse_custom_id("oublie-moi:10:confirmer"),
+            Some((UserId::new(10), true))
+        );
+        assert_eq!(
+            parse_custom_id("oublie-moi:10:annuler"),
+            Some((UserId::new(10), false))
+        );
+        assert_eq!(parse_custom_id("oublie-moi:10:autre"), None);
+        assert_eq!(parse_custom_id("sondage:10:confirmer"), None);
+    }
+
+    #[tokio::test]
+    async fn test_forget() {
+        let db = Db::memory();
+        for user_id in ["10", "11"] {
+            MuteUsers::insert(&db, &User::builder(user_id.to_owned()))
+                .await
+                .unwrap();
+        }
+        let poll = bson::doc! {"votes": {"10": 1, "11": 0}};
+        db::insert_raw(&db, polls::COLLECTION, poll).await.unwrap();
+
+        let summary = forget(&db, UserId::new(10)).await.unwrap();
+        assert_eq!(summary.iter().map(|(_, count)| count).sum::<u64>(), 2);
+        let users = MuteUsers::all(&db).await.unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].user_id, "11");
+        let polls = db::raw_documents(&db, polls::COLLECTION, bson::doc! {})
+            .await
+            .unwrap();
+        assert_eq!(
+            polls[0].get_document("votes").unwrap(),
+            &bson::doc! {"11": 0}
+        );
+        assert!(summary_text(&forget(&db, UserId::new(10)).await.unwrap()).contains("aucune"));
+    }
+}
@@ -0,0 +1,465 @@
+use std::collections::HashMap;
+
+use bson::doc;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use futures::FutureExt;
+use mongodb::bson::oid::ObjectId;
+use serenity::all::{
+    ButtonStyle, ChannelId, ComponentInteraction, CreateActionRow, CreateAllowedMentions,
+    CreateButton, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+    CreateInteractionResponseMessage, CreateMessage, EditMessage, MessageId, UserId,
+};
+use serenity::prelude::Context;
+use tracing::error;
+
+use crate::collections::{check, Collection};
+use crate::datetime;
+use crate::db::{self, Db, DbError};
+use crate::forget::Hook;
+
+pub const COLLECTION: &str = "game_nights";
+// custom ids of the answer buttons are "soiree:<game night id>:<answer>"
+pub const BUTTON_PREFIX: &str = "soiree";
+
+// attendees are pinged this long before the start
+const REMIND_BEFORE: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Rsvp {
+    Present,
+    Maybe,
+    Absent,
+}
+
+impl Rsvp {
+    const ALL: [Self; 3] = [Self::Present, Self::Maybe, Self::Absent];
+
+    const fn id(self) -> &'static str {
+        match self {
+            Self::Present => "present",
+            Self::Maybe => "peutetre",
+            Self::Absent => "absent",
+        }
+    }
+
+    fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|rsvp| rsvp.id() == id)
+    }
+
+    const fn label(self) -> &'static str {
+        match self {
+            Self::Present => "Présent",
+            Self::Maybe => "Peut-être",
+            Self::Absent => "Absent",
+        }
+    }
+
+    const fn style(self) -> ButtonStyle {
+        match self {
+            Self::Present => ButtonStyle::Success,
+            Self::Maybe => ButtonStyle::Secondary,
+            Self::Absent => ButtonStyle::Danger,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct GameNight {
+    _id: ObjectId,
+    pub guild_id: Option<String>,
+    pub channel_id: String,
+    pub message_id: String,
+    pub game: String,
+    pub organizer_id: String,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub starts_at: DateTime<Utc>,
+    pub weekly: bool,
+    // user id -> answer
+    pub answers: HashMap<String, Rsvp>,
+    pub reminded: bool,
+}
+
+impl GameNight {
+    pub fn builder(
+        guild_id: Option<String>,
+        channel_id: String,
+        game: String,
+        organizer_id: String,
+        starts_at: DateTime<Utc>,
+        weekly: bool,
+    ) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            guild_id,
+            channel_id,
+            message_id: String::new(),
+            game,
+            organizer_id,
+            starts_at,
+            weekly,
+            answers: HashMap::new(),
+            reminded: false,
+        }
+    }
+
+    pub fn id(&self) -> String {
+        self._id.to_hex()
+    }
+
+    fn channel(&self) -> Option<ChannelId> {
+        self.channel_id
+            .parse::<u64>()
+            .ok()
+            .filter(|id| *id != 0)
+            .map(ChannelId::new)
+    }
+
+    // user ids with this answer, sorted to keep the embed stable
+    pub fn attendees(&self, rsvp: Rsvp) -> Vec<&str> {
+        let mut ids: Vec<&str> = self
+            .answers
+            .iter()
+            .filter(|(_, answer)| **answer == rsvp)
+            .map(|(id, _)| id.as_str())
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    pub fn embed(&self) -> CreateEmbed {
+        let start = self.starts_at.timestamp();
+        let mut embed = CreateEmbed::new()
+            .title(format!("Soirée {}", self.game))
+            .description(format!(
+                "<t:{start}:F> (<t:{start}:R>)\nOrganisée par <@{}>",
+                self.organizer_id
+            ))
+            .color(serenity::model::Colour::PURPLE);
+        for rsvp in Rsvp::ALL {
+            let attendees = self.attendees(rsvp);
+            let value = if attendees.is_empty() {
+                "Personne".to_owned()
+            } else {
+                attendees
+                    .iter()
+                    .map(|id| format!("<@{id}>"))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            };
+            embed = embed.field(
+                format!("{} ({})", rsvp.label(), attendees.len()),
+                value,
+                true,
+            );
+        }
+        if self.weekly {
+            embed = embed.footer(CreateEmbedFooter::new("Toutes les semaines"));
+        }
+        embed
+    }
+
+    pub fn buttons(&self) -> Vec<CreateActionRow> {
+        let buttons = Rsvp::ALL
+            .into_iter()
+            .map(|rsvp| {
+                CreateButton::new(format!("{BUTTON_PREFIX}:{}:{}", self.id(), rsvp.id()))
+                    .label(rsvp.label())
+                    .style(rsvp.style())
+            })
+            .collect();
+        vec![CreateActionRow::Buttons(buttons)]
+    }
+
+    // the attendees who answered present or maybe, pinged before the start
+    fn pinged(&self) -> Vec<&str> {
+        let mut ids = self.attendees(Rsvp::Present);
+        ids.extend(self.attendees(Rsvp::Maybe));
+        ids
+    }
+
+    pub fn reminder(&self) -> String {
+        let pings: Vec<String> = self
+            .pinged()
+            .into_iter()
+            .map(|id| format!("<@{id}>"))
+            .collect();
+        let start = self.starts_at.timestamp();
+        if pings.is_empty() {
+            format!(
+                "La soirée {} commence <t:{start}:R>, personne n'a répondu présent",
+                self.game
+            )
+        } else {
+            format!(
+                "{} La soirée {} commence <t:{start}:R> !",
+                pings.join(" "),
+                self.game
+            )
+        }
+    }
+}
+
+// first weekly occurrence after now, at the same french time across daylight saving changes
+pub fn next_occurrence(starts_at: DateTime<Utc>, now: DateTime<Utc>) -> DateTime<Utc> {
+    let mut local = starts_at.with_timezone(&datetime::TIMEZONE).naive_local();
+    loop {
+        let Some(next) = local.checked_add_signed(Duration::days(7)) else {
+            return DateTime::<Utc>::MAX_UTC;
+        };
+        local = next;
+        // a time skipped by the change to summer time is moved an hour later
+        let at = datetime::TIMEZONE
+            .from_local_datetime(&local)
+            .earliest()
+            .or_else(|| {
+                datetime::TIMEZONE
+                    .from_local_datetime(&(local + Duration::hours(1)))
+                    .earliest()
+            });
+        if let Some(at) = at.map(|at| at.with_timezone(&Utc)) {
+            if at > now {
+                return at;
+            }
+        }
+    }
+}
+
+pub fn parse_custom_id(custom_id: &str) -> Option<(ObjectId, Rsvp)> {
+    let mut parts = custom_id.split(':');
+    if parts.next()? != BUTTON_PREFIX {
+        return None;
+    }
+    let id = ObjectId::parse_str(parts.next()?).ok()?;
+    let rsvp = Rsvp::from_id(parts.next()?)?;
+    parts.next().is_none().then_some((id, rsvp))
+}
+
+async fn find(db: &Db, id: ObjectId) -> Result<Option<GameNight>, DbError> {
+    db::find_filter(db, COLLECTION, doc! {"_id": id}).await
+}
+
+// records the answer of a button click and updates the attendee lists
+pub async fn answer(
+    ctx: &Context,
+    db: &Db,
+    interaction: &ComponentInteraction,
+) -> Result<(), serenity::Error> {
+    let Some((id, rsvp)) = parse_custom_id(&interaction.data.custom_id) else {
+        return Ok(());
+    };
+    let Ok(answer) = bson::to_bson(&rsvp) else {
+        return Ok(());
+    };
+    let update = doc! {"$set": {format!("answers.{}", interaction.user.id): answer}};
+    let updated = match db::update_query(db, COLLECTION, doc! {"_id": id}, update).await {
+        Ok(_) => find(db, id).await,
+        Err(e) => Err(e),
+    };
+
+    let response = match updated {
+        Ok(Some(night)) => CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .embed(night.embed())
+                .components(night.buttons()),
+        ),
+        Ok(None) => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content("Cette soirée n'existe plus")
+                .ephemeral(true),
+        ),
+        Err(e) => {
+            error!("error while answering game night {id} : {e}");
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content("Impossible de répondre pour le moment")
+                    .ephemeral(true),
+            )
+        }
+    };
+    interaction.create_response(&ctx.http, response).await
+}
+
+async fn post(ctx: &Context, night: &mut GameNight) -> Result<(), serenity::Error> {
+    let Some(channel_id) = night.channel() else {
+        return Ok(());
+    };
+    let message = channel_id
+        .send_message(
+            &ctx.http,
+            CreateMessage::new()
+                .embed(night.embed())
+                .components(night.buttons()),
+        )
+        .await?;
+    night.message_id = message.id.to_string();
+    Ok(())
+}
+
+// pings attendees of the nights about to start, and moves weekly ones to next week
+pub async fn run_due(ctx: &Context, db: &Db) -> Result<(), DbError> {
+    let now = Utc::now();
+
+    let soon = bson::DateTime::from_chrono(now + Duration::minutes(REMIND_BEFORE));
+    let to_remind: Vec<GameNight> = db::get_objects(
+        db,
+        COLLECTION,
+        doc! {"reminded": false, "starts_at": {"$lte": soon}},
+    )
+    .await?;
+    for night in to_remind {
+        let update = doc! {"$set": {"reminded": true}};
+        db::update_query(db, COLLECTION, doc! {"_id": night._id}, update).await?;
+        // no late pings for nights missed while the bot was offline
+        if night.starts_at > now - Duration::minutes(REMIND_BEFORE) {
+            let Some(channel_id) = night.channel() else {
+                continue;
+            };
+            // the game is written by the organizer, only the attendees can be pinged
+            let users = night
+                .pinged()
+                .into_iter()
+                .filter_map(|id| id.parse::<UserId>().ok());
+            let message = CreateMessage::new()
+                .content(night.reminder())
+                .allowed_mentions(CreateAllowedMentions::new().users(users));
+            if let Err(e) = channel_id.send_message(&ctx.http, message).await {
+                error!("error while reminding game night {} : {e}", night.id());
+            }
+        }
+    }
+
+    let started: Vec<GameNight> = db::get_objects(
+        db,
+        COLLECTION,
+        doc! {"starts_at": {"$lte": bson::DateTime::from_chrono(now)}},
+    )
+    .await?;
+    for mut night in started {
+        // the buttons of a past night are removed
+        if let Err(e) = close_message(ctx, &night).await {
+            error!("error while closing game night {} : {e}", night.id());
+        }
+        if !night.weekly {
+            db::delete(db, COLLECTION, &night).await?;
+            continue;
+        }
+        night.starts_at = next_occurrence(night.starts_at, now);
+        night.answers.clear();
+        night.reminded = false;
+        if let Err(e) = post(ctx, &mut night).await {
+            error!("error while posting game night {} : {e}", night.id());
+        }
+        let update = doc! {"$set": {
+            "starts_at": bson::DateTime::from_chrono(night.starts_at),
+            "answers": {},
+            "reminded": false,
+            "message_id": &night.message_id,
+        }};
+        db::update_query(db, COLLECTION, doc! {"_id": night._id}, update).await?;
+    }
+    Ok(())
+}
+
+// removes the buttons of a started or cancelled night
+pub async fn close_message(ctx: &Context, night: &GameNight) -> Result<(), serenity::Error> {
+    let (Ok(channel_id), Ok(message_id)) = (
+        night.channel_id.parse::<u64>(),
+        night.message_id.parse::<u64>(),
+    ) else {
+        return Ok(());
+    };
+    ChannelId::new(channel_id)
+        .edit_message(
+            &ctx.http,
+            MessageId::new(message_id),
+            EditMessage::new()
+                .embed(night.embed())
+                .components(Vec::new()),
+        )
+        .await?;
+    Ok(())
+}
+
+pub const COLLECTIONS: &[Collection] = &[Collection {
+    name: COLLECTION,
+    model: check::<GameNight>,
+    unique: &[],
+    ttl: None,
+    forget: Some(Hook {
+        label: "soirées jeux",
+        forget: |db, user_id| forget_user(db, user_id).boxed(),
+    }),
+}];
+
+// the nights organized by the user are deleted, their answers to the others removed
+async fn forget_user(db: &Db, user_id: UserId) -> Result<u64, DbError> {
+    let organized = doc! {"organizer_id": user_id.to_string()};
+    let deleted = db::delete_multiple_query(db, COLLECTION, organized).await?;
+    let answer = format!("answers.{user_id}");
+    let filter = doc! {&answer: {"$exists": true}};
+    let update = doc! {"$unset": {&answer: ""}};
+    Ok(deleted + db::update_multiple_query(db, COLLECTION, filter, update).await?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_attendees() {
+        let start = Utc.with_ymd_and_hms(2024, 5, 17, 19, 0, 0).unwrap();
+        let mut night = GameNight::builder(
+            None,
+            "1".to_owned(),
+            "Civ6".to_owned(),
+            "2".to_owned(),
+            start,
+            true,
+        );
+        night.answers.insert("20".to_owned(), Rsvp::Present);
+        night.answers.insert("10".to_owned(), Rsvp::Present);
+        night.answers.insert("30".to_owned(), Rsvp::Maybe);
+        night.answers.insert("40".to_owned(), Rsvp::Absent);
+
+        assert_eq!(night.attendees(Rsvp::Present), vec!["10", "20"]);
+        assert_eq!(night.attendees(Rsvp::Absent), vec!["40"]);
+        assert!(night
+            .reminder()
+            .starts_with("<@10> <@20> <@30> La soirée Civ6"));
+    }
+
+    #[test]
+    fn test_next_occurrence() {
+        let start = Utc.with_ymd_and_hms(2024, 5, 17, 19, 0, 0).unwrap();
+        assert_eq!(next_occurrence(start, start), start + Duration::weeks(1));
+        assert_eq!(
+            next_occurrence(start, start + Duration::days(15)),
+            start + Duration::weeks(3)
+        );
+
+        // 21:00 in Paris on both sides of the last sunday of october and of march
+        let paris = |month, day| {
+            datetime::TIMEZONE
+                .with_ymd_and_hms(2024, month, day, 21, 0, 0)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        assert_eq!(next_occurrence(paris(10, 25), paris(10, 25)), paris(11, 1));
+        assert_eq!(paris(11, 1) - paris(10, 25), Duration::hours(7 * 24 + 1));
+        assert_eq!(next_occurrence(paris(3, 29), paris(3, 29)), paris(4, 5));
+        assert_eq!(paris(4, 5) - paris(3, 29), Duration::hours(7 * 24 - 1));
+    }
+
+    #[test]
+    fn test_custom_id() {
+        let id = ObjectId::new();
+        let custom_id = format!("{BUTTON_PREFIX}:{}:{}", id.to_hex(), Rsvp::Maybe.id());
+        assert_eq!(parse_custom_id(&custom_id), Some((id, Rsvp::Maybe)));
+        assert_eq!(
+            parse_custom_id(&format!("soiree:{}:oui", id.to_hex())),
+            None
+        );
+        assert_eq!(parse_custom_id(&format!("sondage:{}:1", id.to_hex())), None);
+    }
+}
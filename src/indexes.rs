@@ -0,0 +1,75 @@
+use tracing::error;
+
+use crate::collections;
+use crate::db::{self, Db};
+
+// the indexes declared by the collections, at startup an index that can't
+// be created because of existing duplicates is only logged
+pub async fn create(db: &Db) {
+    for collection in collections::all() {
+        let name = collection.name;
+        for fields in collection.unique {
+            if let Err(e) = db::create_unique_index(db, name, fields).await {
+                error!("error while creating the unique index {fields:?} of {name} : {e}");
+            }
+        }
+        if let Some((field, expire_after)) = collection.ttl {
+            if let Err(e) = db::create_ttl_index(db, name, field, expire_after).await {
+                error!("error while creating the ttl index on {field} of {name} : {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::commands::macros::model::{TempMacro, TempMacros};
+    use crate::db::User;
+    use crate::repository::{MuteUsers, Repository};
+
+    #[tokio::test]
+    async fn test_unique_indexes() {
+        let db = Db::memory();
+        create(&db).await;
+        let user = |id: &str| User::builder(id.to_owned());
+
+        MuteUsers::insert(&db, &user("10")).await.unwrap();
+        assert!(MuteUsers::insert(&db, &user("10")).await.is_err());
+        // another object with the same user id is a duplicate too
+        let raw = bson::doc! {"user_id": "10", "extra": true};
+        assert!(
+            db::upsert_query(&db, MuteUsers::COLLECTION, raw.clone(), raw)
+                .await
+                .is_err()
+        );
+        MuteUsers::insert(&db, &user("11")).await.unwrap();
+        assert_eq!(MuteUsers::all(&db).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_ttl_indexes() {
+        let db = Db::memory();
+        create(&db).await;
+        let mut abandoned = bson::to_document(&TempMacro::builder(
+            "10".to_owned(),
+            "roll".to_owned(),
+            None,
+        ))
+        .unwrap();
+        abandoned.insert("created_at", bson::DateTime::from_millis(0));
+        db::insert_raw(&db, TempMacros::COLLECTION, abandoned)
+            .await
+            .unwrap();
+        TempMacros::insert(
+            &db,
+            &TempMacro::builder("11".to_owned(), "roll".to_owned(), None),
+        )
+        .await
+        .unwrap();
+
+        let remaining = TempMacros::all(&db).await.unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].user_id, "11");
+    }
+}
@@ -7,8 +7,9 @@ use serenity::prelude::Context;
 use std::sync::Arc;
 use tracing::error;
 
-use crate::utils;
+use crate::db::Db;
 use crate::LogChanIdContainer;
+use crate::{game_nights, polls, reminders, utils};
 
 pub async fn log_system_load(ctx: Arc<Context>) {
     let time = Local::now().to_rfc2822();
@@ -59,6 +60,21 @@ pub async fn log_system_load(ctx: Arc<Context>) {
     };
 }
 
+pub const SCHEDULER_PERIOD: u64 = 30;
+
+// runs the jobs that are due, called every SCHEDULER_PERIOD seconds
+pub async fn scheduler(ctx: &Context, db: &Db) {
+    if let Err(e) = reminders::deliver_due(ctx, db).await {
+        error!("error while delivering reminders : {e}");
+    }
+    if let Err(e) = polls::close_due(ctx, db).await {
+        error!("error while closing polls : {e}");
+    }
+    if let Err(e) = game_nights::run_due(ctx, db).await {
+        error!("error while updating game nights : {e}");
+    }
+}
+
 pub fn status_loop(ctx: &Arc<Context>) {
     let game = [
         "LoL avec les boys",
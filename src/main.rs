@@ -1,30 +1,61 @@
+mod analytics;
+mod answered;
+mod backup;
 mod bot;
+mod choices;
+mod collections;
 // pub mod command_info;
 mod commands;
 mod containers;
+mod cooldown;
+mod datetime;
 #[allow(clippy::impl_trait_in_params)]
 pub mod db;
+mod emojis;
+mod forget;
+mod game_nights;
+mod indexes;
 // mod framework;
 // TODO: decide what to do with module after macros re-implemented
 // pub mod interaction;
 mod loops;
 mod message;
+mod migrations;
+mod mute;
+mod polls;
+mod quotes;
+mod ratings;
+mod reactions;
+mod reminders;
+mod repository;
 mod secrets;
+mod storage;
+mod template;
+mod text;
 pub mod utils;
 // pub mod web_scraper;
 
 use anyhow::anyhow;
 use commands::{
-    admin::register::register,
+    admin::{backup::admin, reaction::reaction, register::register},
     general::{
         based::{based, based_message, based_user},
+        choose::{choose, choose_prefix},
         hello::hello,
         help::help,
         id::{id, id_user},
         nerd::{nerd, nerd_message},
+        oublie::oublie_moi,
         ping::ping,
+        quote::{quote, quote_message},
+        remind::{remind, remindme},
         roll::{roll, roll_prefix},
         slide::slide,
+        soiree::soiree,
+        sondage::sondage,
+        stats::stats,
+        teams::{match_, teams},
+        tg::tg,
     },
 };
 use serenity::model::prelude::{ChannelId, GuildId};
@@ -33,15 +64,22 @@ use shuttle_runtime::SecretStore;
 use std::env;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+use tracing::error;
 
 use bot::Bot;
 use containers::{
-    DatabaseUri, DatabaseUriContainer, GuildGroup, GuildIdContainer, LogChanIdContainer,
-    ShardManagerContainer, TempChanContainer,
+    AnsweredContainer, CooldownsContainer, EmojiCacheContainer, GuildGroup, GuildIdContainer,
+    LogChanIdContainer, MuteCacheContainer, ReactionRulesContainer, ShardManagerContainer,
+    TempChanContainer,
 };
+use cooldown::Cooldowns;
+use db::Db;
 
+// User data, which is stored and accessible in all command invocations
 #[derive(Debug)]
-pub struct Data {} // User data, which is stored and accessible in all command invocations
+pub struct Data {
+    pub db: Db,
+}
 
 #[shuttle_runtime::main]
 async fn serenity(
@@ -57,19 +95,69 @@ async fn serenity(
         based(),
         based_message(),
         based_user(),
+        choose(),
+        choose_prefix(),
         help(),
         id(),
         id_user(),
         nerd(),
         nerd_message(),
+        oublie_moi(),
         ping(),
+        quote(),
+        quote_message(),
+        remind(),
+        remindme(),
         roll(),
         roll_prefix(),
         slide(),
+        soiree(),
+        sondage(),
+        stats(),
+        teams(),
+        match_(),
+        tg(),
+        reaction(),
         register(),
+        admin(),
     ];
     bot::apply_desc_from(&mut commands, "fr");
 
+    // "sqlite" keeps everything in a single file (SQLITE_PATH),
+    // "memory" runs the bot locally without mongodb, nothing is kept after it stops
+    let storage: String = secrets::parse_or(&secret_store, "STORAGE", "mongodb".to_owned())?;
+    let db = match storage.as_str() {
+        "mongodb" => Db::connect(&secrets::get(&secret_store, "DATABASE_URI")?)
+            .await
+            .map_err(|e| anyhow!("Error connecting to the database : {e}"))?,
+        "sqlite" => {
+            let path: String =
+                secrets::parse_or(&secret_store, "SQLITE_PATH", "teamy.sqlite".to_owned())?;
+            Db::sqlite(&path).map_err(|e| anyhow!("Error opening the sqlite database : {e}"))?
+        }
+        "memory" => Db::memory(),
+        other => {
+            return Err(
+                anyhow!("'STORAGE' should be mongodb, sqlite or memory, not {other}").into(),
+            )
+        }
+    };
+
+    // in dry run, pending migrations are only logged and nothing is written,
+    // the bot doesn't start on data it can't read
+    let dry_run = secrets::parse_or(&secret_store, "MIGRATIONS_DRY_RUN", false)?;
+    let pending = migrations::run(&db, dry_run)
+        .await
+        .map_err(|e| anyhow!("Error while migrating the database : {e}"))?;
+    if dry_run && !pending.is_empty() {
+        return Err(anyhow!(
+            "{} migration(s) pending, not starting in dry run : unset MIGRATIONS_DRY_RUN to apply them",
+            pending.len()
+        )
+        .into());
+    }
+    indexes::create(&db).await;
+
     // Create framework for bot
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
@@ -82,6 +170,7 @@ async fn serenity(
                 case_insensitive_commands: true,
                 ..Default::default()
             },
+            on_error: |error| Box::pin(bot::on_error(error)),
             event_handler: |ctx, event, framework, data| {
                 Box::pin(bot::event_handler(ctx, event, framework, data))
             },
@@ -90,13 +179,24 @@ async fn serenity(
         .setup(|ctx, _ready, framework| {
             Box::pin(async move {
                 bot::register_guild(ctx, framework).await;
-                Ok(Data {})
+                if let Err(e) = mute::load(ctx, &db).await {
+                    error!("error while loading muted users, chans and guilds : {e}");
+                }
+                if let Err(e) = cooldown::load(ctx, &db).await {
+                    error!("error while loading reaction cooldowns : {e}");
+                }
+                bot::load_emojis(ctx).await;
+                bot::spawn_scheduler(ctx, db.clone());
+                Ok(Data { db })
             })
         })
         .build();
 
     // Set gateway intents, which decides what events the bot will be notified about
-    let intents = GatewayIntents::GUILD_MESSAGES
+    let intents = GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::GUILD_VOICE_STATES
+        | GatewayIntents::GUILD_EMOJIS_AND_STICKERS
         | GatewayIntents::MESSAGE_CONTENT
         | GatewayIntents::DIRECT_MESSAGES;
 
@@ -113,16 +213,20 @@ async fn serenity(
         "GUILD_ID",
     )?);
     let log_chan = ChannelId::new(secrets::parse(&secret_store, "LOG_CHAN_ID")?);
-    let db_uri = DatabaseUri(secrets::get(&secret_store, "DATABASE_URI")?);
     let temp_chan = ChannelId::new(secrets::parse(&secret_store, "TEMP_CHAN")?);
+    let persist_cooldowns = secrets::parse_or(&secret_store, "PERSIST_REACTION_COOLDOWNS", false)?;
 
     {
         let mut data = client.data.write().await;
         data.insert::<ShardManagerContainer>(Arc::clone(&client.shard_manager));
         data.insert::<GuildIdContainer>(Arc::new(guild_group));
         data.insert::<LogChanIdContainer>(Arc::new(log_chan));
-        data.insert::<DatabaseUriContainer>(Arc::new(db_uri));
         data.insert::<TempChanContainer>(Arc::new(temp_chan));
+        data.insert::<ReactionRulesContainer>(Arc::default());
+        data.insert::<MuteCacheContainer>(Arc::default());
+        data.insert::<EmojiCacheContainer>(Arc::default());
+        data.insert::<AnsweredContainer>(Arc::default());
+        data.insert::<CooldownsContainer>(Arc::new(Mutex::new(Cooldowns::new(persist_cooldowns))));
     }
 
     Ok(client.into())
@@ -1,9 +1,18 @@
-use crate::{db, utils};
+use crate::choices;
+use crate::cooldown::{self, Limits};
+use crate::db::Db;
+use crate::reactions::ReactionRule;
+use crate::template::{self, Values};
+use crate::text::Text;
+use crate::{analytics, emojis, mute, reactions, utils};
 use rand::seq::SliceRandom;
+use rand::Rng;
 use serenity::{
     model::{channel::Message, prelude::*},
     prelude::*,
 };
+use std::collections::HashMap;
+use tracing::error;
 
 pub static SALUTATIONS: [&str; 4] = ["Bonjour", "Salut", "Coucou", "Yo"];
 
@@ -39,40 +48,16 @@ impl From<ReactionConversionError> for HandleMessageError {
     }
 }
 
-fn substring_count(string: &str, targets: &[&str]) -> usize {
-    targets
-        .iter()
-        .filter(|t| string.contains(&t.to_lowercase()))
-        .count()
+pub fn endwith(text: &Text, targets: &[&str]) -> bool {
+    text.ends_with(targets)
 }
 
-fn fullword_count(string: &str, targets: &[&str]) -> usize {
-    let lowercase_targets: Vec<String> = targets.iter().map(|s| s.to_lowercase()).collect();
-    let compare: Vec<&str> = lowercase_targets
-        .iter()
-        .map(std::string::String::as_str)
-        .collect();
-    string
-        .split_whitespace()
-        .filter(|word| compare.contains(word))
-        .count()
-}
-
-fn endwith(string: &str, targets: &[&str]) -> bool {
-    for target in targets {
-        if string.ends_with(target) {
-            return true;
-        }
-    }
-    false
-}
-
-fn present(string: &str, targets: &[&str]) -> bool {
-    fullword_count(string, targets) > 0_usize
+pub fn present(text: &Text, targets: &[&str]) -> bool {
+    text.has_word(targets)
 }
 
-fn present_words(string: &str, targets: &[&str]) -> bool {
-    substring_count(string, targets) > 0_usize
+pub fn present_words(text: &Text, targets: &[&str]) -> bool {
+    text.has_substring(targets)
 }
 
 fn _capitalize(s: &str) -> String {
@@ -82,198 +67,484 @@ fn _capitalize(s: &str) -> String {
     })
 }
 
-fn choose<'a>(choices: &[&'a str]) -> &'a str {
-    choices.choose(&mut rand::thread_rng()).unwrap_or(&"")
+// "{random:a|b|c}"
+pub fn random_template(choices: &[&str]) -> String {
+    format!("{{random:{}}}", choices.join("|"))
 }
 
 async fn find_emoji(ctx: &Context, guild: Option<GuildId>, name: &str) -> Option<Emoji> {
-    if let Some(guild_id) = guild {
-        let Ok(emojis) = guild_id.emojis(&ctx.http).await else {
-            return None;
-        };
-        emojis.iter().find(|e| e.name == name).cloned()
-    } else {
-        None
-    }
+    emojis::find(ctx, guild?, name).await
 }
 
-async fn emoji_or(ctx: &Context, guild_id: Option<GuildId>, name: &str) -> String {
-    (find_emoji(ctx, guild_id, name).await).map_or_else(
-        || format!("<veuillez imaginer l'emoji \"{name}\">"),
-        |emoji| emoji.to_string(),
-    )
+// guild emojis used by a template, resolved before rendering it
+pub async fn template_emojis(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    template: &str,
+) -> HashMap<String, String> {
+    let mut emojis = HashMap::new();
+    for name in template::emoji_names(template) {
+        if let Some(emoji) = find_emoji(ctx, guild_id, &name).await {
+            emojis.insert(name, emoji.to_string());
+        }
+    }
+    emojis
 }
 
-fn bot(message: &str) -> bool {
-    present(message, &["bot", "robot", "teamy"])
+fn bot(text: &Text) -> bool {
+    present(text, &["bot", "robot", "teamy"])
 }
 
-fn ou(message: &str) -> Option<&str> {
-    let mut options = message.split(" ou ");
+// picks one of the options around the " ou " of the message, without weights
+// so that "18:30 ou 19:00" stays two hours
+fn ou<R: Rng>(rng: &mut R, message: &str) -> Option<String> {
     let Ok(re) = regex::Regex::new(r"bot|robot|teamy") else {
         return None;
     };
-    let a = re.split(options.next()?).last()?;
-    let b = re.split(options.next()?).next()?;
-    Some(choose(&[a, b]))
+    let options = re.split(message).find(|part| part.contains(" ou "))?;
+    let choices = choices::parse_options(options, false).ok()?;
+    let drawn = choices::draw(rng, &choices, 1);
+    drawn.first().map(|choice| choice.label.clone())
 }
 
-// true if is mute and shouldn't react
-async fn mute_checks(ctx: &Context, msg: &Message) -> bool {
-    (if let Some(guild_id) = msg.guild_id {
-        db::is_object_in_coll(
-            ctx,
-            "mute_guilds",
-            &db::Guild::builder(guild_id.to_string()),
-        )
-        .await
-        .unwrap_or(false)
-    } else {
-        false
-    }) || db::is_object_in_coll(
-        ctx,
-        "mute_chans",
-        &db::Chan::builder(msg.channel_id.to_string()),
-    )
-    .await
-    .unwrap_or(false)
-        || db::is_object_in_coll(
-            ctx,
-            "mute_users",
-            &db::User::builder(msg.author.id.to_string()),
-        )
-        .await
-        .unwrap_or(false)
+// firing limits of the built-in reactions, rules not listed here always fire
+fn builtin_limits(rule: &str) -> Limits {
+    match rule {
+        "quoi" => Limits::new(0.5, 120, 600),
+        "societe" | "sus" => Limits::new(1.0, 60, 0),
+        "source" | "pas_mal" => Limits::new(1.0, 30, 0),
+        _ => Limits::ALWAYS,
+    }
 }
 
-pub async fn handle_reaction(
-    ctx: &Context,
-    msg: &Message,
-) -> Result<Option<String>, HandleMessageError> {
-    let user_message = msg.content.to_lowercase();
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    // template of the reply, see template::render
+    Reply(String),
+    // unicode or custom emoji added as a reaction to the message
+    React(String),
+}
 
-    // FIXME
-    // if mute_checks(ctx, msg).await {
-    //     return Ok(None);
-    // }
+impl Action {
+    const fn is_reply(&self) -> bool {
+        matches!(self, Self::Reply(_))
+    }
+}
 
-    let user_nick = utils::get_user_name(msg.guild_id, ctx.http(), &msg.author).await;
-    let bot = bot(&user_message);
+// a rule matching the message, with what it would do if it fires
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decision {
+    pub rule: String,
+    pub limits: Limits,
+    pub actions: Vec<Action>,
+}
+
+impl Decision {
+    fn builtin(rule: &str, actions: Vec<Action>) -> Self {
+        Self {
+            rule: rule.to_owned(),
+            limits: builtin_limits(rule),
+            actions,
+        }
+    }
+
+    fn reply(rule: &str, reply: &str) -> Self {
+        Self::builtin(rule, vec![Action::Reply(reply.to_owned())])
+    }
+
+    fn reply_emoji(rule: &str, name: &str) -> Self {
+        Self::reply(rule, &format!("{{emoji:{name}}}"))
+    }
+
+    fn guild<R: Rng>(rng: &mut R, rule: &ReactionRule) -> Self {
+        let mut actions: Vec<Action> = rule.emojis.iter().cloned().map(Action::React).collect();
+        if let Some(reply) = rule.replies.choose(rng) {
+            actions.push(Action::Reply(reply.clone()));
+        }
+        Self {
+            rule: rule.key(),
+            limits: rule.limits,
+            actions,
+        }
+    }
+
+    pub fn has_reply(&self) -> bool {
+        self.actions.iter().any(Action::is_reply)
+    }
+}
+
+pub struct MessageInfo<'a> {
+    pub content: &'a str,
+}
+
+// every rule matching the message, in priority order: guild rules, emoji reactions, then replies
+pub fn decide<R: Rng>(rng: &mut R, info: &MessageInfo, rules: &[ReactionRule]) -> Vec<Decision> {
+    let text = Text::new(info.content);
+    let bot = bot(&text);
+    let mut decisions: Vec<Decision> = reactions::matching(rules, &text)
+        .map(|rule| Decision::guild(rng, rule))
+        .collect();
 
     // emoji reactions
     // pirate
-    if present_words(&user_message, &["belle bite"]) {
-        let pirate = ReactionType::try_from("🏴‍☠️")?;
-        let crossed_swords = ReactionType::try_from("⚔️")?;
-        let _: Reaction = msg.react(&ctx.http, pirate).await?;
-        let _: Reaction = msg.react(&ctx.http, crossed_swords).await?;
+    if present_words(&text, &["belle bite"]) {
+        decisions.push(Decision::builtin(
+            "pirate",
+            vec![
+                Action::React("🏴‍☠️".to_owned()),
+                Action::React("⚔️".to_owned()),
+            ],
+        ));
     }
 
     // bengala
-    if present(&user_message, &["bengala"]) {
-        let _: Reaction = msg.react(&ctx.http, '🍆').await?;
+    if present(&text, &["bengala"]) {
+        decisions.push(Decision::builtin(
+            "bengala",
+            vec![Action::React("🍆".to_owned())],
+        ));
     }
 
     // string reactions
     // bonjour bot
-    if bot && present(&user_message, &SALUTATIONS) {
-        return Ok(Some(format!("{} {} !", choose(&SALUTATIONS), user_nick)));
+    if bot && present(&text, &SALUTATIONS) {
+        decisions.push(Decision::reply(
+            "bonjour",
+            &format!("{} {{user}} !", random_template(&SALUTATIONS)),
+        ));
     }
 
     // societer
-    if present(
-        &user_message,
-        &["société", "societe", "societer", "saucisse"],
-    ) {
-        return Ok(Some(emoji_or(ctx, msg.guild_id, "saucisse").await));
+    if present(&text, &["société", "societe", "societer", "saucisse"]) {
+        decisions.push(Decision::reply_emoji("societe", "saucisse"));
     }
 
     // sus
-    if present(&user_message, &["sus", "sussy"]) {
-        return Ok(Some(emoji_or(ctx, msg.guild_id, "afungus").await));
+    if present(&text, &["sus", "sussy"]) {
+        decisions.push(Decision::reply_emoji("sus", "afungus"));
     }
 
     // civ bedge
-    if present(&user_message, &["attend", "attends", "attendre"])
-        && present(&user_message, &["civ"])
-        && present(&user_message, &["Thomas"])
+    if present(&text, &["attend", "attends", "attendre"])
+        && present(&text, &["civ"])
+        && present(&text, &["Thomas"])
     {
-        return Ok(Some(emoji_or(ctx, msg.guild_id, "bedge").await));
+        decisions.push(Decision::reply_emoji("civ", "bedge"));
     }
 
     // cum
-    if present(&user_message, &["cum", "cummies", "cummy"]) {
-        return Ok(Some(":milk:".to_owned()));
+    if present(&text, &["cum", "cummies", "cummy"]) {
+        decisions.push(Decision::reply("cum", ":milk:"));
     }
 
     // source
-    if present_words(&user_message, &["source ?", "sources ?"]) {
-        return Ok(Some(
-            choose(&[
-                "Ça m'est apparu dans un rêve",
-                "Contexte ?",
-                "Moi",
-                "La Laitière",
-                "Manuel Valls",
-                "Mon cul",
-                "Le ciel me l'a dit",
-                "Trust me bro",
-                "Do your own research",
-                "J'ai appris ça sur Internet",
-            ])
-            .to_owned(),
-        ));
+    if present_words(&text, &["source ?", "sources ?", "source?", "sources?"]) {
+        let reply = random_template(&[
+            "Ça m'est apparu dans un rêve",
+            "Contexte ?",
+            "Moi",
+            "La Laitière",
+            "Manuel Valls",
+            "Mon cul",
+            "Le ciel me l'a dit",
+            "Trust me bro",
+            "Do your own research",
+            "J'ai appris ça sur Internet",
+        ]);
+        decisions.push(Decision::reply("source", &reply));
     }
 
     // pas mal non
-    if present_words(&user_message, &["pas mal non"]) {
-        return Ok(Some("C'est français :flag_fr:".to_owned()));
+    if present_words(&text, &["pas mal non"]) {
+        decisions.push(Decision::reply("pas_mal", "C'est français :flag_fr:"));
     }
 
     // quoi
-    if endwith(&user_message, &["quoi", "quoi ?"]) {
-        return Ok(Some(choose(&["quoicoubeh", "feur"]).to_owned()));
+    if endwith(&text, &["quoi", "quoi ?"]) {
+        let reply = random_template(&["quoicoubeh", "feur"]);
+        decisions.push(Decision::reply("quoi", &reply));
     }
 
     // good bot
-    if bot && present(&user_message, &["bon", "good", "gentil", "nice"]) {
-        return Ok(Some(
-            choose(&[
-                ":smiley:",
-                ":smile:",
-                ":grin:",
-                ":blush:",
-                ":smiling_face_with_3_hearts:",
-            ])
-            .to_owned(),
-        ));
+    if bot && present(&text, &["bon", "good", "gentil", "nice"]) {
+        let reply = random_template(&[
+            ":smiley:",
+            ":smile:",
+            ":grin:",
+            ":blush:",
+            ":smiling_face_with_3_hearts:",
+        ]);
+        decisions.push(Decision::reply("good_bot", &reply));
     }
 
     // bad bot
-    if bot && present(&user_message, &["bad", "mauvais", "méchant"]) {
-        let reaction = choose(&[
-            ":nerd:",
+    if bot && present(&text, &["bad", "mauvais", "méchant"]) {
+        // the nerd answer mocks the message
+        let reply = random_template(&[
+            "{nerd:{message}}",
             ":pensive:",
             ":worried:",
             ":slight_frown:",
             ":frowning2:",
             ":cry:",
         ]);
-        return match reaction {
-            ":nerd:" => Ok(Some(utils::nerdify(&user_message))),
-            _ => Ok(Some(reaction.to_owned())),
-        };
+        decisions.push(Decision::reply("bad_bot", &reply));
     }
 
     // gay bot
-    if bot && present(&user_message, &["gay"]) {
-        return Ok(Some(choose(&[":hot_face:", ":shushing_face:"]).to_owned()));
+    if bot && present(&text, &["gay"]) {
+        let reply = random_template(&[":hot_face:", ":shushing_face:"]);
+        decisions.push(Decision::reply("gay_bot", &reply));
     }
 
-    // ou
-    if bot && present(&user_message, &["ou"]) {
-        return Ok(Some(ou(&user_message).unwrap_or("").to_owned()));
+    // ou, "où" is normalized to "ou" too but has no options to choose from
+    if bot && present(&text, &["ou"]) {
+        let lowercase = text.stripped.to_lowercase();
+        if let Some(reply) = ou(rng, &lowercase) {
+            decisions.push(Decision::reply("ou", &template::escape(&reply)));
+        }
+    }
+
+    decisions
+}
+
+// executes the decisions allowed by their limits, until one of them replies
+pub async fn handle_reaction(
+    ctx: &Context,
+    db: &Db,
+    msg: &Message,
+) -> Result<Option<String>, HandleMessageError> {
+    if mute::message_muted(ctx, msg.guild_id, msg.channel_id, msg.author.id).await {
+        return Ok(None);
+    }
+
+    let rules = if let Some(guild_id) = msg.guild_id {
+        reactions::guild_rules(ctx, db, guild_id)
+            .await
+            .unwrap_or_else(|e| {
+                error!("error while getting reaction rules of guild {guild_id} : {e}");
+                Vec::new()
+            })
+    } else {
+        Vec::new()
+    };
+    let info = MessageInfo {
+        content: &msg.content,
+    };
+    let decisions = decide(&mut rand::thread_rng(), &info, &rules);
+
+    for decision in decisions {
+        if !cooldown::allowed(
+            ctx,
+            db,
+            &decision.rule,
+            msg.channel_id,
+            msg.author.id,
+            decision.limits,
+        )
+        .await
+        {
+            continue;
+        }
+        if let Err(e) = analytics::record(
+            db,
+            &decision.rule,
+            msg.guild_id,
+            msg.channel_id,
+            msg.author.id,
+        )
+        .await
+        {
+            error!("error while recording reaction {} : {e}", decision.rule);
+        }
+        for action in decision.actions {
+            match action {
+                Action::React(emoji) => {
+                    let reaction = ReactionType::try_from(emoji.as_str())?;
+                    let _: Reaction = msg.react(&ctx.http, reaction).await?;
+                }
+                Action::Reply(reply) => return Ok(Some(render_reply(ctx, msg, &reply).await)),
+            }
+        }
     }
 
     Ok(None)
 }
+
+async fn render_reply(ctx: &Context, msg: &Message, reply: &str) -> String {
+    let user_name = utils::get_user_name(msg.guild_id, ctx.http(), &msg.author).await;
+    let channel = msg.channel_id.mention().to_string();
+    let message = msg.content.to_lowercase();
+    let values = Values {
+        user: &user_name,
+        channel: &channel,
+        message: &message,
+        emojis: template_emojis(ctx, msg.guild_id, reply).await,
+    };
+    template::render(&mut rand::thread_rng(), reply, &values)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::reactions::MatchKind;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    // rule and rendered text of the first reply
+    fn first_reply(content: &str, rules: &[ReactionRule]) -> Option<(String, String)> {
+        let mut rng = StdRng::seed_from_u64(0);
+        let decision = decide(&mut rng, &MessageInfo { content }, rules)
+            .into_iter()
+            .find(Decision::has_reply)?;
+        let Some(Action::Reply(reply)) = decision.actions.into_iter().find(Action::is_reply) else {
+            return None;
+        };
+        let message = content.to_lowercase();
+        let values = Values {
+            user: "Jean",
+            message: &message,
+            ..Values::default()
+        };
+        Some((decision.rule, template::render(&mut rng, &reply, &values)))
+    }
+
+    #[test]
+    fn test_replies() {
+        let emoji = |name: &str| vec![template::imagine(name)];
+        let replies =
+            |choices: &[&str]| -> Vec<String> { choices.iter().map(|c| (*c).to_owned()).collect() };
+        let cases = [
+            (
+                "salut teamy",
+                "bonjour",
+                replies(&[
+                    "Bonjour Jean !",
+                    "Salut Jean !",
+                    "Coucou Jean !",
+                    "Yo Jean !",
+                ]),
+            ),
+            ("La société.", "societe", emoji("saucisse")),
+            ("une saucisse", "societe", emoji("saucisse")),
+            ("t'es sus!", "sus", emoji("afungus")),
+            ("Thomas attend pour civ", "civ", emoji("bedge")),
+            ("cummy", "cum", replies(&[":milk:"])),
+            (
+                "pas mal non ?",
+                "pas_mal",
+                replies(&["C'est français :flag_fr:"]),
+            ),
+            ("tu fais quoi ?", "quoi", replies(&["quoicoubeh", "feur"])),
+            ("pourquoi", "quoi", replies(&["quoicoubeh", "feur"])),
+            (
+                "good bot",
+                "good_bot",
+                replies(&[
+                    ":smiley:",
+                    ":smile:",
+                    ":grin:",
+                    ":blush:",
+                    ":smiling_face_with_3_hearts:",
+                ]),
+            ),
+            (
+                "gay bot",
+                "gay_bot",
+                replies(&[":hot_face:", ":shushing_face:"]),
+            ),
+            ("teamy pizza ou sushi", "ou", replies(&["pizza", "sushi"])),
+            (
+                "teamy, pizza ou sushi ou kebab ?",
+                "ou",
+                replies(&["pizza", "sushi", "kebab ?"]),
+            ),
+            ("teamy 18:30 ou 19:00", "ou", replies(&["18:30", "19:00"])),
+            (
+                "teamy {user} ou {channel}",
+                "ou",
+                replies(&["{user}", "{channel}"]),
+            ),
+            (
+                "méchant robot",
+                "bad_bot",
+                replies(&[
+                    "mÉcHaNt rObOt",
+                    ":pensive:",
+                    ":worried:",
+                    ":slight_frown:",
+                    ":frowning2:",
+                    ":cry:",
+                ]),
+            ),
+        ];
+        for (content, rule, choices) in cases {
+            let (fired, action) =
+                first_reply(content, &[]).unwrap_or_else(|| panic!("message: {content}"));
+            assert_eq!(fired, rule, "message: {content}");
+            assert!(
+                choices.contains(&action),
+                "message: {content}, reply: {action:?}"
+            );
+        }
+
+        let fired = first_reply("source ?", &[]).map(|(rule, _)| rule);
+        assert_eq!(fired.as_deref(), Some("source"));
+
+        for content in [
+            "rien à voir",
+            "`sus` dans du code",
+            "> sus\ncité",
+            "quoi de neuf",
+            "teamy t'es où ?",
+        ] {
+            assert_eq!(first_reply(content, &[]), None, "message: {content}");
+        }
+    }
+
+    #[test]
+    fn test_ou_without_options() {
+        let info = MessageInfo {
+            content: "teamy t'es où ?",
+        };
+        let decisions = decide(&mut StdRng::seed_from_u64(0), &info, &[]);
+        assert!(!decisions.iter().any(|d| d.rule == "ou"));
+    }
+
+    #[test]
+    fn test_reactions() {
+        let info = MessageInfo {
+            content: "belle bite de bengala",
+        };
+        let decisions = decide(&mut StdRng::seed_from_u64(0), &info, &[]);
+        let rules: Vec<&str> = decisions.iter().map(|d| d.rule.as_str()).collect();
+        assert_eq!(rules, vec!["pirate", "bengala"]);
+        assert!(!decisions.iter().any(Decision::has_reply));
+    }
+
+    #[test]
+    fn test_guild_rules() {
+        let rule = ReactionRule::builder(
+            "1".to_owned(),
+            "ping".to_owned(),
+            MatchKind::Word,
+            vec!["ping".to_owned()],
+            vec!["pong".to_owned()],
+            vec!["🏓".to_owned()],
+            Limits::ALWAYS,
+        );
+        let info = MessageInfo {
+            content: "ping quoi",
+        };
+        let decisions = decide(&mut StdRng::seed_from_u64(0), &info, &[rule]);
+        assert_eq!(decisions.len(), 2);
+        assert_eq!(decisions[0].rule, "1:ping");
+        assert_eq!(
+            decisions[0].actions,
+            vec![
+                Action::React("🏓".to_owned()),
+                Action::Reply("pong".to_owned())
+            ]
+        );
+        assert_eq!(decisions[1].rule, "quoi");
+    }
+}
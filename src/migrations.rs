@@ -0,0 +1,219 @@
+use bson::{doc, Bson, Document};
+use tracing::info;
+
+use crate::collections::{check, Collection};
+use crate::commands::macros::model::TempMacros;
+use crate::commands::pdx::model::{PdxFollows, PdxGame, PdxLinksRepo};
+use crate::db::{self, Db, DbError};
+use crate::repository::Repository;
+
+pub const COLLECTION: &str = "schema_version";
+
+// the stored version, only deserialized to check the restored archives
+#[derive(Debug, serde::Deserialize)]
+struct SchemaVersion {
+    #[allow(dead_code)]
+    version: i64,
+}
+
+// backed up so that an old archive is migrated after being restored
+pub const COLLECTIONS: &[Collection] = &[Collection {
+    name: COLLECTION,
+    model: check::<SchemaVersion>,
+    unique: &[],
+    ttl: None,
+    forget: None,
+}];
+
+// a change of the stored documents of a collection, applied once and in order,
+// a released step must never be edited: add a new one instead
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    pub collection: &'static str,
+    // returns true if the document was changed
+    pub apply: fn(&mut Document) -> bool,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "jeux de PdxGame manquants ou inconnus dans les liens",
+        collection: PdxLinksRepo::COLLECTION,
+        apply: sync_pdx_links,
+    },
+    Migration {
+        version: 2,
+        description: "jeux de PdxGame manquants ou inconnus dans les abonnements",
+        collection: PdxFollows::COLLECTION,
+        apply: sync_pdx_follows,
+    },
+    Migration {
+        version: 3,
+        description: "date de création manquante des macros temporaires",
+        collection: TempMacros::COLLECTION,
+        apply: date_temp_macros,
+    },
+];
+
+// what a migration changed, or would change in dry run
+#[derive(Debug, PartialEq, Eq)]
+pub struct Report {
+    pub version: u32,
+    pub description: &'static str,
+    pub changed: usize,
+}
+
+pub async fn version(db: &Db) -> Result<u32, DbError> {
+    let stored = db::raw_documents(db, COLLECTION, doc! {}).await?;
+    Ok(stored
+        .first()
+        .and_then(|d| d.get_i64("version").ok())
+        .and_then(|v| u32::try_from(v).ok())
+        .unwrap_or(0))
+}
+
+// applies the steps newer than the stored version, in dry run nothing is written
+pub async fn run(db: &Db, dry_run: bool) -> Result<Vec<Report>, DbError> {
+    let current = version(db).await?;
+    let mut reports = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let mut changed = Vec::new();
+        for mut document in db::raw_documents(db, migration.collection, doc! {}).await? {
+            if (migration.apply)(&mut document) {
+                changed.push(document);
+            }
+        }
+        let report = Report {
+            version: migration.version,
+            description: migration.description,
+            changed: changed.len(),
+        };
+        if dry_run {
+            info!(
+                "migration {} ({}) : {} document(s) de {} seraient modifiés",
+                report.version, report.description, report.changed, migration.collection
+            );
+        } else {
+            for document in changed {
+                db::replace_raw(db, migration.collection, document).await?;
+            }
+            let update = doc! {"$set": {"version": i64::from(migration.version)}};
+            db::upsert_query(db, COLLECTION, doc! {}, update).await?;
+            info!(
+                "migration {} ({}) : {} document(s) de {} modifiés",
+                report.version, report.description, report.changed, migration.collection
+            );
+        }
+        reports.push(report);
+    }
+    Ok(reports)
+}
+
+fn known_games() -> Vec<Bson> {
+    PdxGame::iterator()
+        .filter_map(|game| bson::to_bson(&game).ok())
+        .collect()
+}
+
+// keeps the entries of known games, in order, and adds the missing ones
+fn sync_games(
+    entries: &mut Vec<Bson>,
+    game_of: fn(&Bson) -> Option<&Bson>,
+    missing: fn(Bson) -> Bson,
+) -> bool {
+    let games = known_games();
+    let synced: Vec<Bson> = games
+        .iter()
+        .map(|game| {
+            entries
+                .iter()
+                .find(|entry| game_of(entry) == Some(game))
+                .cloned()
+                .unwrap_or_else(|| missing(game.clone()))
+        })
+        .collect();
+    let changed = *entries != synced;
+    *entries = synced;
+    changed
+}
+
+// games without links yet get empty ones, filled by the next /pdx dd
+fn sync_pdx_links(document: &mut Document) -> bool {
+    let Ok(games) = document.get_array_mut("games") else {
+        return false;
+    };
+    sync_games(
+        games,
+        |entry| entry.as_document()?.get("game"),
+        |game| doc! {"game": game, "latest": "", "previous": ""}.into(),
+    )
+}
+
+// new games are followed by default, like in PdxFollow::new
+fn sync_pdx_follows(document: &mut Document) -> bool {
+    let Ok(follows) = document.get_array_mut("follows") else {
+        return false;
+    };
+    sync_games(
+        follows,
+        |entry| entry.as_array()?.first(),
+        |game| Bson::Array(vec![game, Bson::Boolean(true)]),
+    )
+}
+
+// the older ones are dated from the migration, so they expire like the new ones
+fn date_temp_macros(document: &mut Document) -> bool {
+    if document.contains_key("created_at") {
+        return false;
+    }
+    document.insert("created_at", bson::DateTime::now());
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::commands::pdx::model::{PdxFollow, PdxLinks};
+
+    #[test]
+    fn test_sync_pdx() {
+        let mut links = bson::to_document(&PdxLinks::init()).unwrap();
+        assert!(!sync_pdx_links(&mut links));
+        let games = links.get_array_mut("games").unwrap();
+        games.remove(0);
+        games.push(doc! {"game": "Imperator", "latest": "", "previous": ""}.into());
+        assert!(sync_pdx_links(&mut links));
+        assert!(bson::from_document::<PdxLinks>(links).is_ok());
+
+        let mut follows = bson::to_document(&PdxFollow::new("10".to_owned())).unwrap();
+        follows
+            .get_array_mut("follows")
+            .unwrap()
+            .push(Bson::Array(vec!["Imperator".into(), true.into()]));
+        assert!(sync_pdx_follows(&mut follows));
+        assert!(!sync_pdx_follows(&mut follows));
+        assert!(bson::from_document::<PdxFollow>(follows).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let db = Db::memory();
+        let mut follow = bson::to_document(&PdxFollow::new("10".to_owned())).unwrap();
+        follow.get_array_mut("follows").unwrap().pop();
+        db::upsert_query(&db, PdxFollows::COLLECTION, doc! {"user_id": "10"}, follow)
+            .await
+            .unwrap();
+
+        let dry = run(&db, true).await.unwrap();
+        assert_eq!(dry.len(), MIGRATIONS.len());
+        assert_eq!(dry[1].changed, 1);
+        assert_eq!(version(&db).await.unwrap(), 0);
+
+        let applied = run(&db, false).await.unwrap();
+        assert_eq!(applied, dry);
+        assert_eq!(version(&db).await.unwrap(), 3);
+        assert!(PdxFollows::all(&db).await.is_ok());
+        assert!(run(&db, false).await.unwrap().is_empty());
+    }
+}
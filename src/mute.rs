@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+
+use futures::FutureExt;
+
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
+use serenity::prelude::Context;
+use tracing::error;
+
+use crate::collections::{check, Collection};
+use crate::db::{Chan, Db, DbError, Guild, User};
+use crate::forget::Hook;
+use crate::repository::{ByUser, MuteChans, MuteGuilds, MuteUsers, Repository};
+use crate::MuteCacheContainer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    User(UserId),
+    Chan(ChannelId),
+    Guild(GuildId),
+}
+
+// in-memory copy of the mute collections, so that messages don't need database lookups
+#[derive(Debug, Default)]
+pub struct MuteCache {
+    users: HashSet<UserId>,
+    chans: HashSet<ChannelId>,
+    guilds: HashSet<GuildId>,
+}
+
+impl MuteCache {
+    pub fn is_muted(&self, target: Target) -> bool {
+        match target {
+            Target::User(id) => self.users.contains(&id),
+            Target::Chan(id) => self.chans.contains(&id),
+            Target::Guild(id) => self.guilds.contains(&id),
+        }
+    }
+
+    pub fn set(&mut self, target: Target, muted: bool) {
+        match (target, muted) {
+            (Target::User(id), true) => _ = self.users.insert(id),
+            (Target::User(id), false) => _ = self.users.remove(&id),
+            (Target::Chan(id), true) => _ = self.chans.insert(id),
+            (Target::Chan(id), false) => _ = self.chans.remove(&id),
+            (Target::Guild(id), true) => _ = self.guilds.insert(id),
+            (Target::Guild(id), false) => _ = self.guilds.remove(&id),
+        }
+    }
+
+    // true if the bot shouldn't react to a message with these ids
+    pub fn message_muted(
+        &self,
+        guild_id: Option<GuildId>,
+        chan_id: ChannelId,
+        user_id: UserId,
+    ) -> bool {
+        guild_id.map_or(false, |id| self.is_muted(Target::Guild(id)))
+            || self.is_muted(Target::Chan(chan_id))
+            || self.is_muted(Target::User(user_id))
+    }
+}
+
+// fills the cache from the database, used at startup
+pub async fn load(ctx: &Context, db: &Db) -> Result<(), DbError> {
+    let users = MuteUsers::all(db).await?;
+    let chans = MuteChans::all(db).await?;
+    let guilds = MuteGuilds::all(db).await?;
+
+    let data = ctx.data.read().await;
+    let Some(cache) = data.get::<MuteCacheContainer>() else {
+        return Err(DbError::internal("no mute cache"));
+    };
+    let mut cache = cache.write().await;
+    cache.users = users
+        .iter()
+        .filter_map(|u| u.user_id.parse().ok())
+        .collect();
+    cache.chans = chans
+        .iter()
+        .filter_map(|c| c.channel_id.parse().ok())
+        .collect();
+    cache.guilds = guilds
+        .iter()
+        .filter_map(|g| g.guild_id.parse().ok())
+        .collect();
+    Ok(())
+}
+
+pub async fn is_muted(ctx: &Context, target: Target) -> bool {
+    let data = ctx.data.read().await;
+    let Some(cache) = data.get::<MuteCacheContainer>() else {
+        error!("there was a problem getting the mute cache");
+        return false;
+    };
+    let cache = cache.read().await;
+    cache.is_muted(target)
+}
+
+pub async fn message_muted(
+    ctx: &Context,
+    guild_id: Option<GuildId>,
+    chan_id: ChannelId,
+    user_id: UserId,
+) -> bool {
+    let data = ctx.data.read().await;
+    let Some(cache) = data.get::<MuteCacheContainer>() else {
+        error!("there was a problem getting the mute cache");
+        return false;
+    };
+    let cache = cache.read().await;
+    cache.message_muted(guild_id, chan_id, user_id)
+}
+
+// returns true if the target was muted before the toggle
+pub async fn toggle(ctx: &Context, db: &Db, target: Target) -> Result<bool, DbError> {
+    let was_muted = match target {
+        Target::User(id) => toggle_object::<MuteUsers>(db, User::builder(id.to_string())).await?,
+        Target::Chan(id) => toggle_object::<MuteChans>(db, Chan::builder(id.to_string())).await?,
+        Target::Guild(id) => {
+            toggle_object::<MuteGuilds>(db, Guild::builder(id.to_string())).await?
+        }
+    };
+
+    let data = ctx.data.read().await;
+    if let Some(cache) = data.get::<MuteCacheContainer>() {
+        cache.write().await.set(target, !was_muted);
+    }
+    Ok(was_muted)
+}
+
+async fn toggle_object<R: Repository>(db: &Db, object: R::Model) -> Result<bool, DbError> {
+    if R::contains(db, &object).await? {
+        R::delete(db, &object).await?;
+        Ok(true)
+    } else {
+        R::insert(db, &object).await?;
+        Ok(false)
+    }
+}
+
+// chans and guilds are muted by admins, only the user's own mute is personal
+pub const COLLECTIONS: &[Collection] = &[
+    Collection {
+        name: MuteUsers::COLLECTION,
+        model: check::<User>,
+        unique: &[&["user_id"]],
+        ttl: None,
+        forget: Some(Hook {
+            label: "réponses désactivées",
+            forget: |db, user_id| MuteUsers::delete_many(db, ByUser(user_id)).boxed(),
+        }),
+    },
+    Collection {
+        name: MuteChans::COLLECTION,
+        model: check::<Chan>,
+        unique: &[&["channel_id"]],
+        ttl: None,
+        forget: None,
+    },
+    Collection {
+        name: MuteGuilds::COLLECTION,
+        model: check::<Guild>,
+        unique: &[&["guild_id"]],
+        ttl: None,
+        forget: None,
+    },
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mute_cache() {
+        let mut cache = MuteCache::default();
+        let guild = GuildId::new(1);
+        let chan = ChannelId::new(2);
+        let user = UserId::new(3);
+
+        assert!(!cache.message_muted(Some(guild), chan, user));
+        cache.set(Target::User(user), true);
+        assert!(cache.message_muted(None, chan, user));
+        assert!(!cache.message_muted(Some(guild), chan, UserId::new(4)));
+        cache.set(Target::User(user), false);
+        cache.set(Target::Guild(guild), true);
+        assert!(cache.message_muted(Some(guild), chan, user));
+        assert!(!cache.message_muted(None, chan, user));
+    }
+
+    #[tokio::test]
+    async fn test_toggle_object() {
+        let db = Db::memory();
+        let user = || User::builder("3".to_owned());
+
+        assert!(!toggle_object::<MuteUsers>(&db, user()).await.unwrap());
+        assert_eq!(MuteUsers::all(&db).await.unwrap().len(), 1);
+        assert!(MuteChans::all(&db).await.unwrap().is_empty());
+        assert!(toggle_object::<MuteUsers>(&db, user()).await.unwrap());
+        assert!(MuteUsers::all(&db).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::HashMap;
+
+use bson::doc;
+use chrono::{DateTime, Utc};
+use futures::FutureExt;
+use mongodb::bson::oid::ObjectId;
+use serenity::all::{
+    ButtonStyle, ChannelId, ComponentInteraction, CreateActionRow, CreateAllowedMentions,
+    CreateButton, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage,
+    CreateMessage, EditMessage, MessageId,
+};
+use serenity::prelude::Context;
+use tracing::error;
+
+use crate::collections::{check, Collection};
+use crate::db::{self, Db, DbError};
+use crate::forget::Hook;
+
+pub const COLLECTION: &str = "polls";
+// custom ids of the vote buttons are "sondage:<poll id>:<option index>"
+pub const BUTTON_PREFIX: &str = "sondage";
+pub const MAX_OPTIONS: usize = 10;
+
+const BUTTONS_PER_ROW: usize = 5;
+// button labels are limited by discord
+const MAX_LABEL: usize = 80;
+const BAR_LENGTH: usize = 10;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Poll {
+    _id: ObjectId,
+    pub guild_id: Option<String>,
+    pub channel_id: String,
+    pub message_id: String,
+    pub question: String,
+    pub options: Vec<String>,
+    // user id -> index of the chosen option
+    pub votes: HashMap<String, u32>,
+    pub closes_at: Option<bson::DateTime>,
+    pub closed: bool,
+}
+
+impl Poll {
+    pub fn builder(
+        guild_id: Option<String>,
+        channel_id: String,
+        question: String,
+        options: Vec<String>,
+        closes_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            guild_id,
+            channel_id,
+            message_id: String::new(),
+            question,
+            options,
+            votes: HashMap::new(),
+            closes_at: closes_at.map(bson::DateTime::from_chrono),
+            closed: false,
+        }
+    }
+
+    pub fn id(&self) -> String {
+        self._id.to_hex()
+    }
+
+    pub fn counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.options.len()];
+        for choice in self.votes.values() {
+            if let Some(count) = usize::try_from(*choice)
+                .ok()
+                .and_then(|i| counts.get_mut(i))
+            {
+                *count += 1;
+            }
+        }
+        counts
+    }
+
+    // indexes of the most voted options, empty without votes
+    pub fn winners(&self) -> Vec<usize> {
+        let counts = self.counts();
+        let max = counts.iter().copied().max().unwrap_or(0);
+        if max == 0 {
+            return Vec::new();
+        }
+        (0..counts.len()).filter(|i| counts[*i] == max).collect()
+    }
+
+    pub fn embed(&self) -> CreateEmbed {
+        let counts = self.counts();
+        let total = self.votes.len();
+        let mut lines: Vec<String> = self
+            .options
+            .iter()
+            .zip(&counts)
+            .enumerate()
+            .map(|(i, (option, count))| {
+                let bar = "█".repeat((count * BAR_LENGTH).div_ceil(total.max(1)));
+                format!("**{}. {option}** — {count} vote(s)\n{bar}", i + 1)
+            })
+            .collect();
+        lines.push(String::new());
+        lines.push(match (self.closed, self.closes_at) {
+            (true, _) => format!("Sondage terminé, {total} vote(s)"),
+            (false, Some(at)) => format!(
+                "{total} vote(s), fin <t:{}:R>",
+                at.timestamp_millis() / 1000
+            ),
+            (false, None) => format!("{total} vote(s)"),
+        });
+        CreateEmbed::new()
+            .title(&self.question)
+            .description(lines.join("\n"))
+            .color(serenity::model::Colour::PURPLE)
+    }
+
+    pub fn buttons(&self) -> Vec<CreateActionRow> {
+        if self.closed {
+            return Vec::new();
+        }
+        let buttons: Vec<CreateButton> = self
+            .options
+            .iter()
+            .enumerate()
+            .map(|(i, option)| {
+                CreateButton::new(custom_id(&self.id(), i))
+                    .label(option.chars().take(MAX_LABEL).collect::<String>())
+                    .style(ButtonStyle::Primary)
+            })
+            .collect();
+        buttons
+            .chunks(BUTTONS_PER_ROW)
+            .map(|row| CreateActionRow::Buttons(row.to_vec()))
+            .collect()
+    }
+
+    pub fn result(&self) -> String {
+        let winners: Vec<&str> = self
+            .winners()
+            .into_iter()
+            .filter_map(|i| self.options.get(i).map(String::as_str))
+            .collect();
+        match winners.as_slice() {
+            [] => format!("Sondage « {} » terminé, personne n'a voté", self.question),
+            [winner] => format!(
+                "Sondage « {} » terminé : **{winner}** l'emporte",
+                self.question
+            ),
+            _ => format!(
+                "Sondage « {} » terminé : égalité entre **{}**",
+                self.question,
+                winners.join("** et **")
+            ),
+        }
+    }
+}
+
+// "a | b, c" -> ["a", "b", "c"]
+pub fn parse_options(s: &str) -> Vec<String> {
+    s.split(['|', ','])
+        .map(str::trim)
+        .filter(|option| !option.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+pub fn custom_id(poll_id: &str, option: usize) -> String {
+    format!("{BUTTON_PREFIX}:{poll_id}:{option}")
+}
+
+pub fn parse_custom_id(custom_id: &str) -> Option<(ObjectId, u32)> {
+    let mut parts = custom_id.split(':');
+    if parts.next()? != BUTTON_PREFIX {
+        return None;
+    }
+    let poll_id = ObjectId::parse_str(parts.next()?).ok()?;
+    let option = parts.next()?.parse().ok()?;
+    parts.next().is_none().then_some((poll_id, option))
+}
+
+// records the vote of a button click and updates the counts of the poll message
+pub async fn vote(
+    ctx: &Context,
+    db: &Db,
+    interaction: &ComponentInteraction,
+) -> Result<(), serenity::Error> {
+    let Some((poll_id, option)) = parse_custom_id(&interaction.data.custom_id) else {
+        return Ok(());
+    };
+    let poll = match find(db, poll_id).await {
+        Ok(Some(poll))
+            if !poll.closed && usize::try_from(option).is_ok_and(|i| i < poll.options.len()) =>
+        {
+            let update = doc! {"$set": {format!("votes.{}", interaction.user.id): option}};
+            match db::update_query(db, COLLECTION, doc! {"_id": poll_id}, update).await {
+                Ok(_) => find(db, poll_id).await.ok().flatten(),
+                Err(e) => {
+                    error!("error while voting on poll {poll_id} : {e}");
+                    None
+                }
+            }
+        }
+        Ok(Some(_)) => {
+            return respond_ephemeral(ctx, interaction, "Ce sondage est terminé").await;
+        }
+        Ok(None) => None,
+        Err(e) => {
+            error!("error while getting poll {poll_id} : {e}");
+            None
+        }
+    };
+
+    match poll {
+        Some(poll) => {
+            let message = CreateInteractionResponseMessage::new()
+                .embed(poll.embed())
+                .components(poll.buttons());
+            interaction
+                .create_response(&ctx.http, CreateInteractionResponse::UpdateMessage(message))
+                .await
+        }
+        None => respond_ephemeral(ctx, interaction, "Impossible de voter pour le moment").await,
+    }
+}
+
+async fn respond_ephemeral(
+    ctx: &Context,
+    interaction: &ComponentInteraction,
+    content: &str,
+) -> Result<(), serenity::Error> {
+    let message = CreateInteractionResponseMessage::new()
+        .content(content)
+        .ephemeral(true);
+    interaction
+        .create_response(&ctx.http, CreateInteractionResponse::Message(message))
+        .await
+}
+
+async fn find(db: &Db, poll_id: ObjectId) -> Result<Option<Poll>, DbError> {
+    db::find_filter(db, COLLECTION, doc! {"_id": poll_id}).await
+}
+
+// closes the polls whose time ran out and announces their result
+pub async fn close_due(ctx: &Context, db: &Db) -> Result<(), DbError> {
+    let filter = doc! {
+        "closed": false,
+        "closes_at": {"$lte": bson::DateTime::now()},
+    };
+    let due: Vec<Poll> = db::get_objects(db, COLLECTION, filter).await?;
+    for mut poll in due {
+        let update = doc! {"$set": {"closed": true}};
+        db::update_query(db, COLLECTION, doc! {"_id": poll._id}, update).await?;
+        poll.closed = true;
+        if let Err(e) = announce(ctx, &poll).await {
+            error!("error while closing poll {} : {e}", poll.id());
+        }
+    }
+    Ok(())
+}
+
+async fn announce(ctx: &Context, poll: &Poll) -> Result<(), serenity::Error> {
+    let (Ok(channel_id), Ok(message_id)) = (
+        poll.channel_id.parse::<u64>(),
+        poll.message_id.parse::<u64>(),
+    ) else {
+        return Ok(());
+    };
+    let channel_id = ChannelId::new(channel_id);
+    channel_id
+        .edit_message(
+            &ctx.http,
+            MessageId::new(message_id),
+            EditMessage::new()
+                .embed(poll.embed())
+                .components(poll.buttons()),
+        )
+        .await?;
+    // the question and the options are written by users, nobody is pinged
+    let result = CreateMessage::new()
+        .content(poll.result())
+        .allowed_mentions(CreateAllowedMentions::new());
+    let _ = channel_id.send_message(&ctx.http, result).await?;
+    Ok(())
+}
+
+// the votes are removed, so the counts of the poll messages change on the next vote
+pub const COLLECTIONS: &[Collection] = &[Collection {
+    name: COLLECTION,
+    model: check::<Poll>,
+    unique: &[],
+    ttl: None,
+    forget: Some(Hook {
+        label: "votes aux sondages",
+        forget: |db, user_id| {
+            let vote = format!("votes.{user_id}");
+            let filter = doc! {&vote: {"$exists": true}};
+            let update = doc! {"$unset": {&vote: ""}};
+            db::update_multiple_query(db, COLLECTION, filter, update).boxed()
+        },
+    }),
+}];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_votes() {
+        let mut poll = Poll::builder(
+            None,
+            "1".to_owned(),
+            "Ce soir ?".to_owned(),
+            parse_options("pizza | sushi, kebab"),
+            None,
+        );
+        assert_eq!(poll.options, vec!["pizza", "sushi", "kebab"]);
+        assert!(poll.winners().is_empty());
+        assert!(poll.result().ends_with("personne n'a voté"));
+
+        poll.votes.insert("10".to_owned(), 1);
+        poll.votes.insert("11".to_owned(), 1);
+        poll.votes.insert("12".to_owned(), 0);
+        // changed vote
+        poll.votes.insert("12".to_owned(), 2);
+        assert_eq!(poll.counts(), vec![0, 2, 1]);
+        assert_eq!(poll.winners(), vec![1]);
+        assert!(poll.result().ends_with("**sushi** l'emporte"));
+
+        poll.votes.insert("13".to_owned(), 2);
+        assert_eq!(poll.winners(), vec![1, 2]);
+        assert!(poll
+            .result()
+            .ends_with("égalité entre **sushi** et **kebab**"));
+    }
+
+    #[test]
+    fn test_custom_id() {
+        let poll_id = ObjectId::new();
+        let id = custom_id(&poll_id.to_hex(), 3);
+        assert_eq!(parse_custom_id(&id), Some((poll_id, 3)));
+        assert_eq!(parse_custom_id("soiree:1:2"), None);
+        assert_eq!(parse_custom_id("sondage:pas-un-id:2"), None);
+        assert_eq!(parse_custom_id(&format!("{id}:4")), None);
+    }
+}
@@ -0,0 +1,149 @@
+use bson::doc;
+use chrono::{DateTime, Utc};
+use futures::FutureExt;
+use mongodb::bson::oid::ObjectId;
+use serenity::model::prelude::{GuildId, Message, UserId};
+
+use crate::collections::{check, Collection};
+use crate::db::{self, Db, DbError};
+use crate::forget::Hook;
+use crate::text;
+
+pub const COLLECTION: &str = "quotes";
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Quote {
+    _id: ObjectId,
+    pub guild_id: String,
+    pub channel_id: String,
+    pub message_id: String,
+    pub author_id: String,
+    pub author_name: String,
+    pub content: String,
+    pub saved_by: String,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub at: DateTime<Utc>,
+    // shown to users to designate the quote
+    pub number: u32,
+}
+
+impl Quote {
+    pub fn builder(
+        guild_id: GuildId,
+        message: &Message,
+        author_name: String,
+        saved_by: UserId,
+    ) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            guild_id: guild_id.to_string(),
+            channel_id: message.channel_id.to_string(),
+            message_id: message.id.to_string(),
+            author_id: message.author.id.to_string(),
+            author_name,
+            content: message.content.clone(),
+            saved_by: saved_by.to_string(),
+            at: *message.timestamp,
+            // given by insert
+            number: 0,
+        }
+    }
+
+    pub fn link(&self) -> String {
+        format!(
+            "https://discord.com/channels/{}/{}/{}",
+            self.guild_id, self.channel_id, self.message_id
+        )
+    }
+}
+
+pub async fn guild_quotes(db: &Db, guild_id: GuildId) -> Result<Vec<Quote>, DbError> {
+    db::get_objects(db, COLLECTION, doc! {"guild_id": guild_id.to_string()}).await
+}
+
+pub fn next_number(quotes: &[Quote]) -> u32 {
+    quotes.iter().map(|q| q.number).max().unwrap_or(0) + 1
+}
+
+// saves the quote with the next number of its guild, see db::INSERT_ATTEMPTS
+pub async fn insert(db: &Db, mut quote: Quote) -> Result<Quote, DbError> {
+    let filter = doc! {"guild_id": &quote.guild_id};
+    let mut attempt = 1;
+    loop {
+        quote.number = next_number(&db::get_objects(db, COLLECTION, filter.clone()).await?);
+        match db::insert(db, COLLECTION, &quote).await {
+            Err(DbError::AlreadyExists) if attempt < db::INSERT_ATTEMPTS => attempt += 1,
+            result => return result.map(|_| quote),
+        }
+    }
+}
+
+// quotes containing the query, ignoring case and accents
+pub fn search<'a>(quotes: &'a [Quote], query: &str) -> Vec<&'a Quote> {
+    let query = text::normalize(query);
+    quotes
+        .iter()
+        .filter(|q| {
+            text::normalize(&q.content).contains(&query)
+                || text::normalize(&q.author_name).contains(&query)
+        })
+        .collect()
+}
+
+pub const COLLECTIONS: &[Collection] = &[Collection {
+    name: COLLECTION,
+    model: check::<Quote>,
+    unique: &[&["guild_id", "message_id"], &["guild_id", "number"]],
+    ttl: None,
+    forget: Some(Hook {
+        label: "citations",
+        forget: |db, user_id| forget_user(db, user_id).boxed(),
+    }),
+}];
+
+// the user's messages are deleted, the quotes they saved from others are kept anonymously
+async fn forget_user(db: &Db, user_id: UserId) -> Result<u64, DbError> {
+    let user_id = user_id.to_string();
+    let deleted = db::delete_multiple_query(db, COLLECTION, doc! {"author_id": &user_id}).await?;
+    let update = doc! {"$set": {"saved_by": ""}};
+    let anonymized =
+        db::update_multiple_query(db, COLLECTION, doc! {"saved_by": &user_id}, update).await?;
+    Ok(deleted + anonymized)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn quote(number: u32, author_name: &str, content: &str) -> Quote {
+        Quote {
+            _id: ObjectId::new(),
+            guild_id: "1".to_owned(),
+            channel_id: "2".to_owned(),
+            message_id: number.to_string(),
+            author_id: "3".to_owned(),
+            author_name: author_name.to_owned(),
+            content: content.to_owned(),
+            saved_by: "4".to_owned(),
+            at: Utc::now(),
+            number,
+        }
+    }
+
+    #[test]
+    fn test_search() {
+        let quotes = [
+            quote(1, "Jean", "La société, c'est la saucisse"),
+            quote(3, "Thomas", "j'attends pour civ"),
+        ];
+        let numbers =
+            |query| -> Vec<u32> { search(&quotes, query).iter().map(|q| q.number).collect() };
+
+        assert_eq!(numbers("SOCIETE"), vec![1]);
+        assert_eq!(numbers("thomas"), vec![3]);
+        assert_eq!(numbers("c"), vec![1, 3]);
+        assert!(numbers("pizza").is_empty());
+        assert_eq!(next_number(&quotes), 4);
+        assert_eq!(next_number(&[]), 1);
+    }
+}
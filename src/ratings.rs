@@ -0,0 +1,173 @@
+use bson::doc;
+use futures::FutureExt;
+use mongodb::bson::oid::ObjectId;
+use serenity::model::prelude::{GuildId, UserId};
+
+use crate::collections::{check, Collection};
+use crate::db::{self, Db, DbError};
+use crate::forget::Hook;
+
+pub const COLLECTION: &str = "ratings";
+pub const DEFAULT_RATING: f64 = 1000.0;
+// how much a single match moves the ratings
+const K_FACTOR: f64 = 32.0;
+
+// elo rating of a player for one game in one guild
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Rating {
+    _id: ObjectId,
+    pub guild_id: String,
+    pub game: String,
+    pub user_id: String,
+    pub rating: f64,
+    pub games: u32,
+}
+
+// players of a team with their rating
+pub type Team = Vec<(UserId, f64)>;
+
+pub fn total(team: &[(UserId, f64)]) -> f64 {
+    team.iter().map(|(_, rating)| rating).sum()
+}
+
+fn average(ratings: &[f64]) -> f64 {
+    if ratings.is_empty() {
+        DEFAULT_RATING
+    } else {
+        ratings.iter().sum::<f64>() / ratings.len() as f64
+    }
+}
+
+// greedy split: best players first, each one to the weakest of the smallest teams
+pub fn balance(players: &[(UserId, f64)], count: usize) -> Vec<Team> {
+    let mut teams: Vec<Team> = vec![Vec::new(); count.max(1)];
+    let mut sorted = players.to_vec();
+    sorted.sort_by(|a, b| b.1.total_cmp(&a.1));
+    for player in sorted {
+        let smallest = teams.iter().map(Vec::len).min().unwrap_or(0);
+        if let Some(team) = teams
+            .iter_mut()
+            .filter(|team| team.len() == smallest)
+            .min_by(|a, b| total(a).total_cmp(&total(b)))
+        {
+            team.push(player);
+        }
+    }
+    teams
+}
+
+// rating won by each winner and lost by each loser, elo on the team averages
+pub fn elo_delta(winners: &[f64], losers: &[f64]) -> f64 {
+    let expected = 1.0 / (1.0 + 10.0_f64.powf((average(losers) - average(winners)) / 400.0));
+    K_FACTOR * (1.0 - expected)
+}
+
+async fn find(
+    db: &Db,
+    guild_id: GuildId,
+    game: &str,
+    user_id: UserId,
+) -> Result<Option<Rating>, DbError> {
+    let filter =
+        doc! {"guild_id": guild_id.to_string(), "game": game, "user_id": user_id.to_string()};
+    db::find_filter(db, COLLECTION, filter).await
+}
+
+// ratings of the players, players who never played have the default rating
+pub async fn get(
+    db: &Db,
+    guild_id: GuildId,
+    game: &str,
+    players: &[UserId],
+) -> Result<Vec<(UserId, f64)>, DbError> {
+    let mut ratings = Vec::new();
+    for user_id in players {
+        let rating = find(db, guild_id, game, *user_id)
+            .await?
+            .map_or(DEFAULT_RATING, |r| r.rating);
+        ratings.push((*user_id, rating));
+    }
+    Ok(ratings)
+}
+
+// atomic, so that concurrent matches of the same player all count
+pub async fn add(
+    db: &Db,
+    guild_id: GuildId,
+    game: &str,
+    user_id: UserId,
+    delta: f64,
+) -> Result<(), DbError> {
+    let query =
+        doc! {"guild_id": guild_id.to_string(), "game": game, "user_id": user_id.to_string()};
+    let mut first = query.clone();
+    first.insert("_id", ObjectId::new());
+    first.insert("rating", DEFAULT_RATING);
+    first.insert("games", 0);
+    db::insert_raw_if_absent(db, COLLECTION, query.clone(), first).await?;
+    let update = doc! {"$inc": {"rating": delta, "games": 1}};
+    db::update_query(db, COLLECTION, query, update).await?;
+    Ok(())
+}
+
+pub const COLLECTIONS: &[Collection] = &[Collection {
+    name: COLLECTION,
+    model: check::<Rating>,
+    unique: &[&["guild_id", "game", "user_id"]],
+    ttl: None,
+    forget: Some(Hook {
+        label: "classements",
+        forget: |db, user_id| {
+            db::delete_multiple_query(db, COLLECTION, doc! {"user_id": user_id.to_string()}).boxed()
+        },
+    }),
+}];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_balance() {
+        let players: Vec<(UserId, f64)> = [1200.0, 1100.0, 1000.0, 1000.0, 900.0, 800.0]
+            .into_iter()
+            .enumerate()
+            .map(|(i, rating)| (UserId::new(i as u64 + 1), rating))
+            .collect();
+
+        let teams = balance(&players, 2);
+        assert_eq!(teams.len(), 2);
+        assert!(teams.iter().all(|team| team.len() == 3));
+        assert!((total(&teams[0]) - total(&teams[1])).abs() <= 100.0);
+
+        let teams = balance(&players[..5], 3);
+        let mut sizes: Vec<usize> = teams.iter().map(Vec::len).collect();
+        sizes.sort_unstable();
+        assert_eq!(sizes, vec![1, 2, 2]);
+    }
+
+    #[test]
+    fn test_elo_delta() {
+        assert!((elo_delta(&[1000.0], &[1000.0]) - 16.0).abs() < 1e-9);
+        // beating a stronger team gives more
+        assert!(elo_delta(&[1000.0], &[1400.0]) > 16.0);
+        assert!(elo_delta(&[1400.0, 1400.0], &[1000.0]) < 16.0);
+    }
+
+    #[tokio::test]
+    async fn test_add() {
+        let db = Db::memory();
+        let guild_id = GuildId::new(1);
+        let user_id = UserId::new(10);
+        let (a, b) = futures::join!(
+            add(&db, guild_id, "civ", user_id, 16.0),
+            add(&db, guild_id, "civ", user_id, -4.0)
+        );
+        a.unwrap();
+        b.unwrap();
+
+        let rating = find(&db, guild_id, "civ", user_id).await.unwrap().unwrap();
+        assert!((rating.rating - (DEFAULT_RATING + 12.0)).abs() < 1e-9);
+        assert_eq!(rating.games, 2);
+    }
+}
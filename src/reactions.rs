@@ -0,0 +1,140 @@
+use bson::doc;
+use mongodb::bson::oid::ObjectId;
+use serenity::model::prelude::GuildId;
+use serenity::prelude::Context;
+
+use crate::collections::{check, Collection};
+use crate::cooldown::Limits;
+use crate::db::{self, Db, DbError};
+use crate::text::Text;
+use crate::{message, ReactionRulesContainer};
+
+pub const COLLECTION: &str = "reaction_rules";
+
+pub const COLLECTIONS: &[Collection] = &[Collection {
+    name: COLLECTION,
+    model: check::<ReactionRule>,
+    unique: &[&["guild_id", "name"]],
+    ttl: None,
+    forget: None,
+}];
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize, poise::ChoiceParameter,
+)]
+pub enum MatchKind {
+    #[name = "mot entier"]
+    Word,
+    #[name = "sous-chaîne"]
+    Substring,
+    #[name = "fin de message"]
+    EndsWith,
+}
+
+impl std::fmt::Display for MatchKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Self::Word => "mot entier",
+            Self::Substring => "sous-chaîne",
+            Self::EndsWith => "fin de message",
+        };
+        write!(f, "{s}")
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct ReactionRule {
+    _id: ObjectId,
+    pub guild_id: String,
+    pub name: String,
+    pub kind: MatchKind,
+    pub words: Vec<String>,
+    pub replies: Vec<String>,
+    pub emojis: Vec<String>,
+    #[serde(default)]
+    pub limits: Limits,
+}
+
+impl ReactionRule {
+    pub fn builder(
+        guild_id: String,
+        name: String,
+        kind: MatchKind,
+        words: Vec<String>,
+        replies: Vec<String>,
+        emojis: Vec<String>,
+        limits: Limits,
+    ) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            guild_id,
+            name,
+            kind,
+            words,
+            replies,
+            emojis,
+            limits,
+        }
+    }
+
+    // identifies the rule for cooldowns
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.guild_id, self.name)
+    }
+
+    pub fn matches(&self, text: &Text) -> bool {
+        let words: Vec<&str> = self.words.iter().map(String::as_str).collect();
+        match self.kind {
+            MatchKind::Word => message::present(text, &words),
+            MatchKind::Substring => message::present_words(text, &words),
+            MatchKind::EndsWith => message::endwith(text, &words),
+        }
+    }
+}
+
+pub fn matching<'a>(
+    rules: &'a [ReactionRule],
+    text: &'a Text,
+) -> impl Iterator<Item = &'a ReactionRule> {
+    rules.iter().filter(move |rule| rule.matches(text))
+}
+
+// rules are cached per guild and loaded from the database on first use
+pub async fn guild_rules(
+    ctx: &Context,
+    db: &Db,
+    guild_id: GuildId,
+) -> Result<Vec<ReactionRule>, DbError> {
+    {
+        let data = ctx.data.read().await;
+        if let Some(cache) = data.get::<ReactionRulesContainer>() {
+            if let Some(rules) = cache.read().await.get(&guild_id) {
+                return Ok(rules.clone());
+            }
+        }
+    }
+
+    let filter = doc! {"guild_id": guild_id.to_string()};
+    let rules = db::get_objects::<ReactionRule>(db, COLLECTION, filter).await?;
+
+    let data = ctx.data.read().await;
+    if let Some(cache) = data.get::<ReactionRulesContainer>() {
+        cache.write().await.insert(guild_id, rules.clone());
+    }
+    Ok(rules)
+}
+
+pub async fn invalidate(ctx: &Context, guild_id: GuildId) {
+    let data = ctx.data.read().await;
+    if let Some(cache) = data.get::<ReactionRulesContainer>() {
+        cache.write().await.remove(&guild_id);
+    }
+}
+
+// after the whole collection changed, every guild is loaded again on first use
+pub async fn invalidate_all(ctx: &Context) {
+    let data = ctx.data.read().await;
+    if let Some(cache) = data.get::<ReactionRulesContainer>() {
+        cache.write().await.clear();
+    }
+}
@@ -0,0 +1,173 @@
+use bson::doc;
+use chrono::{DateTime, Duration, Utc};
+use futures::FutureExt;
+use mongodb::bson::oid::ObjectId;
+use serenity::all::{ChannelId, CreateAllowedMentions, CreateMessage, UserId};
+use serenity::prelude::Context;
+use tracing::error;
+
+use crate::collections::{check, Collection};
+use crate::db::{self, Db, DbError};
+use crate::forget::Hook;
+
+pub const COLLECTION: &str = "reminders";
+
+// a reminder delivered later than this was missed while the bot was offline
+const LATE_AFTER: i64 = 120;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Reminder {
+    _id: ObjectId,
+    pub user_id: String,
+    // None when delivered by DM
+    pub channel_id: Option<String>,
+    pub text: String,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub due: DateTime<Utc>,
+    // shown to the user to cancel the reminder
+    pub number: u32,
+}
+
+impl Reminder {
+    pub fn builder(
+        user_id: UserId,
+        channel_id: Option<ChannelId>,
+        text: String,
+        due: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            _id: ObjectId::new(),
+            user_id: user_id.to_string(),
+            channel_id: channel_id.map(|id| id.to_string()),
+            text,
+            due,
+            // given by insert
+            number: 0,
+        }
+    }
+
+    pub fn message(&self, now: DateTime<Utc>) -> String {
+        let late = if now - self.due > Duration::seconds(LATE_AFTER) {
+            "\n(en retard, le bot était hors ligne)"
+        } else {
+            ""
+        };
+        format!(
+            "<@{}> :alarm_clock: Rappel : {}{late}",
+            self.user_id, self.text
+        )
+    }
+}
+
+pub async fn user_reminders(db: &Db, user_id: UserId) -> Result<Vec<Reminder>, DbError> {
+    let mut reminders: Vec<Reminder> =
+        db::get_objects(db, COLLECTION, doc! {"user_id": user_id.to_string()}).await?;
+    reminders.sort_by_key(|r| r.due);
+    Ok(reminders)
+}
+
+pub fn next_number(reminders: &[Reminder]) -> u32 {
+    reminders.iter().map(|r| r.number).max().unwrap_or(0) + 1
+}
+
+// saves the reminder with the next number of its user, retried like quotes::insert
+pub async fn insert(db: &Db, mut reminder: Reminder) -> Result<Reminder, DbError> {
+    let filter = doc! {"user_id": &reminder.user_id};
+    let mut attempt = 1;
+    loop {
+        reminder.number = next_number(&db::get_objects(db, COLLECTION, filter.clone()).await?);
+        match db::insert(db, COLLECTION, &reminder).await {
+            Err(DbError::AlreadyExists) if attempt < db::INSERT_ATTEMPTS => attempt += 1,
+            result => return result.map(|_| reminder),
+        }
+    }
+}
+
+async fn deliver(
+    ctx: &Context,
+    reminder: &Reminder,
+    now: DateTime<Utc>,
+) -> Result<(), serenity::Error> {
+    // the text is written by the user, it can only ping them
+    let mut mentions = CreateAllowedMentions::new();
+    if let Ok(user_id) = reminder.user_id.parse::<UserId>() {
+        mentions = mentions.users([user_id]);
+    }
+    let message = CreateMessage::new()
+        .content(reminder.message(now))
+        .allowed_mentions(mentions);
+    if let Some(channel_id) = reminder
+        .channel_id
+        .as_ref()
+        .and_then(|id| id.parse::<u64>().ok())
+    {
+        let _ = ChannelId::new(channel_id)
+            .send_message(&ctx.http, message)
+            .await?;
+    } else if let Ok(user_id) = reminder.user_id.parse::<u64>() {
+        let _ = UserId::new(user_id)
+            .direct_message(&ctx.http, message)
+            .await?;
+    }
+    Ok(())
+}
+
+// sends every reminder that is due, including the ones missed during a restart
+pub async fn deliver_due(ctx: &Context, db: &Db) -> Result<(), DbError> {
+    let now = Utc::now();
+    let filter = doc! {"due": {"$lte": bson::DateTime::from_chrono(now)}};
+    let due: Vec<Reminder> = db::get_objects(db, COLLECTION, filter).await?;
+    for reminder in due {
+        if let Err(e) = deliver(ctx, &reminder, now).await {
+            error!(
+                "error while delivering reminder of user {} : {e}",
+                reminder.user_id
+            );
+        }
+        // not retried, the channel may not exist anymore
+        db::delete(db, COLLECTION, &reminder).await?;
+    }
+    Ok(())
+}
+
+pub const COLLECTIONS: &[Collection] = &[Collection {
+    name: COLLECTION,
+    model: check::<Reminder>,
+    unique: &[&["user_id", "number"]],
+    ttl: None,
+    forget: Some(Hook {
+        label: "rappels",
+        forget: |db, user_id| {
+            db::delete_multiple_query(db, COLLECTION, doc! {"user_id": user_id.to_string()}).boxed()
+        },
+    }),
+}];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_message() {
+        let due = Utc::now();
+        let mut reminder = Reminder::builder(UserId::new(1), None, "pizza".to_owned(), due);
+        reminder.number = 1;
+        assert_eq!(reminder.message(due), "<@1> :alarm_clock: Rappel : pizza");
+        assert!(reminder
+            .message(due + Duration::hours(1))
+            .ends_with("(en retard, le bot était hors ligne)"));
+        assert_eq!(next_number(&[reminder]), 2);
+    }
+
+    #[tokio::test]
+    async fn test_insert() {
+        let db = Db::memory();
+        crate::indexes::create(&db).await;
+        for expected in 1..=2 {
+            let reminder = Reminder::builder(UserId::new(1), None, "pizza".to_owned(), Utc::now());
+            assert_eq!(insert(&db, reminder).await.unwrap().number, expected);
+        }
+        let other = Reminder::builder(UserId::new(2), None, "pizza".to_owned(), Utc::now());
+        assert_eq!(insert(&db, other).await.unwrap().number, 1);
+    }
+}
@@ -0,0 +1,132 @@
+use bson::{doc, Bson, Document};
+use serenity::model::prelude::{ChannelId, GuildId, UserId};
+
+use crate::db::{self, Chan, Db, DbError, Guild, Model, User};
+
+// a collection with its model and the filters it can be queried with,
+// so that collection names are only written once
+// (some methods are only used by the macro and pdx commands, not compiled yet)
+#[allow(dead_code)]
+pub trait Repository {
+    type Model: Model;
+    type Filter: Into<Document> + Send;
+    const COLLECTION: &'static str;
+
+    async fn find_one(db: &Db, filter: Self::Filter) -> Result<Option<Self::Model>, DbError> {
+        db::find_filter(db, Self::COLLECTION, filter.into()).await
+    }
+
+    async fn find(db: &Db, filter: Self::Filter) -> Result<Vec<Self::Model>, DbError> {
+        db::get_objects(db, Self::COLLECTION, filter.into()).await
+    }
+
+    async fn all(db: &Db) -> Result<Vec<Self::Model>, DbError> {
+        db::get_objects(db, Self::COLLECTION, doc! {}).await
+    }
+
+    async fn contains(db: &Db, object: &Self::Model) -> Result<bool, DbError> {
+        db::is_object_in_coll(db, Self::COLLECTION, object).await
+    }
+
+    // fails if an object with the same fields already exists
+    async fn insert(db: &Db, object: &Self::Model) -> Result<Bson, DbError> {
+        db::insert(db, Self::COLLECTION, object).await
+    }
+
+    async fn update(db: &Db, object: &Self::Model, update: &Document) -> Result<(), DbError> {
+        db::update(db, Self::COLLECTION, object, update).await
+    }
+
+    // atomic, returns the previous version
+    async fn replace(db: &Db, object: &Self::Model) -> Result<Option<Self::Model>, DbError> {
+        db::replace(db, Self::COLLECTION, object).await
+    }
+
+    async fn delete(db: &Db, object: &Self::Model) -> Result<(), DbError> {
+        db::delete(db, Self::COLLECTION, object).await
+    }
+
+    async fn delete_one(db: &Db, filter: Self::Filter) -> Result<(), DbError> {
+        db::delete_query(db, Self::COLLECTION, filter.into()).await
+    }
+
+    // returns the number of deleted objects
+    async fn delete_many(db: &Db, filter: Self::Filter) -> Result<u64, DbError> {
+        db::delete_multiple_query(db, Self::COLLECTION, filter.into()).await
+    }
+}
+
+pub struct ByUser(pub UserId);
+
+impl From<ByUser> for Document {
+    fn from(ByUser(id): ByUser) -> Self {
+        doc! {"user_id": id.to_string()}
+    }
+}
+
+pub struct ByChan(pub ChannelId);
+
+impl From<ByChan> for Document {
+    fn from(ByChan(id): ByChan) -> Self {
+        doc! {"channel_id": id.to_string()}
+    }
+}
+
+pub struct ByGuild(pub GuildId);
+
+impl From<ByGuild> for Document {
+    fn from(ByGuild(id): ByGuild) -> Self {
+        doc! {"guild_id": id.to_string()}
+    }
+}
+
+// matches every document, for collections holding a single one
+pub struct Any;
+
+impl From<Any> for Document {
+    fn from(_: Any) -> Self {
+        doc! {}
+    }
+}
+
+pub struct MuteUsers;
+
+impl Repository for MuteUsers {
+    type Model = User;
+    type Filter = ByUser;
+    const COLLECTION: &'static str = "mute_users";
+}
+
+pub struct MuteChans;
+
+impl Repository for MuteChans {
+    type Model = Chan;
+    type Filter = ByChan;
+    const COLLECTION: &'static str = "mute_chans";
+}
+
+pub struct MuteGuilds;
+
+impl Repository for MuteGuilds {
+    type Model = Guild;
+    type Filter = ByGuild;
+    const COLLECTION: &'static str = "mute_guilds";
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_filters() {
+        assert_eq!(
+            Document::from(ByUser(UserId::new(12))),
+            doc! {"user_id": "12"}
+        );
+        assert_eq!(
+            Document::from(ByGuild(GuildId::new(3))),
+            doc! {"guild_id": "3"}
+        );
+        assert_eq!(Document::from(Any), doc! {});
+    }
+}
@@ -20,6 +20,16 @@ pub fn parse<T: FromStr>(secret_store: &SecretStore, key: &str) -> Result<T, Err
     )
 }
 
+pub fn parse_or<T: FromStr>(secret_store: &SecretStore, key: &str, default: T) -> Result<T, Error> {
+    secret_store.get(key).map_or_else(
+        || Ok(default),
+        |s| {
+            s.parse::<T>()
+                .map_err(|_err| anyhow!("'{key}' should be {}", std::any::type_name::<T>()).into())
+        },
+    )
+}
+
 pub fn parse_objects<T: FromStr, F: From<T>>(
     secret_store: &SecretStore,
     key: &str,
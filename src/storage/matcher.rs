@@ -0,0 +1,345 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bson::{oid::ObjectId, Bson, Document};
+
+use crate::db::DbError;
+
+// the subset of mongodb queries used by the bot, for backends without a query engine:
+// partial documents, dotted paths and $eq, $ne, $lt, $lte, $gt, $gte, $in, $exists
+pub fn matches(document: &Document, filter: &Document) -> bool {
+    filter.iter().all(|(key, condition)| {
+        let value = get_path(document, key);
+        match condition {
+            Bson::Document(operators) if is_operators(operators) => operators
+                .iter()
+                .all(|(op, operand)| compare(op, value, operand)),
+            _ => value == Some(condition),
+        }
+    })
+}
+
+fn is_operators(document: &Document) -> bool {
+    !document.is_empty() && document.keys().all(|k| k.starts_with('$'))
+}
+
+fn compare(op: &str, value: Option<&Bson>, operand: &Bson) -> bool {
+    match op {
+        "$eq" => value == Some(operand),
+        "$ne" => value != Some(operand),
+        "$exists" => matches!(operand, Bson::Boolean(exists) if value.is_some() == *exists),
+        "$in" => {
+            matches!(operand, Bson::Array(options) if value.is_some_and(|v| options.contains(v)))
+        }
+        "$lt" | "$lte" | "$gt" | "$gte" => {
+            let Some(ordering) = value.and_then(|v| order(v, operand)) else {
+                return false;
+            };
+            match op {
+                "$lt" => ordering.is_lt(),
+                "$lte" => ordering.is_le(),
+                "$gt" => ordering.is_gt(),
+                _ => ordering.is_ge(),
+            }
+        }
+        _ => false,
+    }
+}
+
+// only values of the same kind are ordered, numbers of any type together
+fn order(a: &Bson, b: &Bson) -> Option<Ordering> {
+    match (a, b) {
+        (Bson::DateTime(a), Bson::DateTime(b)) => Some(a.cmp(b)),
+        (Bson::String(a), Bson::String(b)) => Some(a.cmp(b)),
+        (Bson::Boolean(a), Bson::Boolean(b)) => Some(a.cmp(b)),
+        _ => number(a)?.partial_cmp(&number(b)?),
+    }
+}
+
+fn number(value: &Bson) -> Option<f64> {
+    match value {
+        Bson::Int32(n) => Some(f64::from(*n)),
+        #[allow(clippy::cast_precision_loss)]
+        Bson::Int64(n) => Some(*n as f64),
+        Bson::Double(n) => Some(*n),
+        _ => None,
+    }
+}
+
+fn get_path<'a>(document: &'a Document, path: &str) -> Option<&'a Bson> {
+    let mut parts = path.split('.');
+    let mut value = document.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_document()?.get(part)?;
+    }
+    Some(value)
+}
+
+fn set_path(document: &mut Document, path: &str, value: Bson) {
+    match path.split_once('.') {
+        Some((head, rest)) => {
+            if !matches!(document.get(head), Some(Bson::Document(_))) {
+                document.insert(head, Document::new());
+            }
+            if let Some(Bson::Document(inner)) = document.get_mut(head) {
+                set_path(inner, rest, value);
+            }
+        }
+        None => _ = document.insert(path, value),
+    }
+}
+
+fn unset_path(document: &mut Document, path: &str) {
+    match path.split_once('.') {
+        Some((head, rest)) => {
+            if let Some(Bson::Document(inner)) = document.get_mut(head) {
+                unset_path(inner, rest);
+            }
+        }
+        None => _ = document.remove(path),
+    }
+}
+
+// applies $set, $unset and $inc, or replaces the document while keeping its _id
+pub fn apply_update(document: &mut Document, update: &Document) -> Result<(), DbError> {
+    if !is_operators(update) {
+        let id = document.get("_id").cloned();
+        *document = update.clone();
+        if let Some(id) = id {
+            document.insert("_id", id);
+        }
+        return Ok(());
+    }
+    for (op, fields) in update {
+        let Bson::Document(fields) = fields else {
+            return Err(DbError::Internal(format!("{op} attend un document")));
+        };
+        for (path, value) in fields {
+            match op.as_str() {
+                "$set" => set_path(document, path, value.clone()),
+                "$unset" => unset_path(document, path),
+                "$inc" => {
+                    let incremented = match (get_path(document, path), value) {
+                        (None, _) => value.clone(),
+                        (Some(Bson::Int32(a)), Bson::Int32(b)) => Bson::Int32(a + b),
+                        (Some(Bson::Int64(a)), Bson::Int32(b)) => Bson::Int64(a + i64::from(*b)),
+                        (Some(Bson::Int64(a)), Bson::Int64(b)) => Bson::Int64(a + b),
+                        (Some(a), b) => match (number(a), number(b)) {
+                            (Some(a), Some(b)) => Bson::Double(a + b),
+                            _ => {
+                                return Err(DbError::Internal(format!(
+                                    "$inc sur {path} non numérique"
+                                )))
+                            }
+                        },
+                    };
+                    set_path(document, path, incremented);
+                }
+                _ => return Err(DbError::Internal(format!("opérateur {op} non supporté"))),
+            }
+        }
+    }
+    Ok(())
+}
+
+// document created by an upsert that matched nothing: the equality fields of the filter, then the update
+pub fn upserted(filter: &Document, update: &Document) -> Result<Document, DbError> {
+    let mut document = Document::new();
+    for (path, condition) in filter {
+        match condition {
+            Bson::Document(operators) if is_operators(operators) => {
+                if let Some(value) = operators.get("$eq") {
+                    set_path(&mut document, path, value.clone());
+                }
+            }
+            _ => set_path(&mut document, path, condition.clone()),
+        }
+    }
+    apply_update(&mut document, update)?;
+    if !document.contains_key("_id") {
+        document.insert("_id", ObjectId::new());
+    }
+    Ok(document)
+}
+
+// unique indexes of the backends without a query engine, checked on every write
+#[derive(Debug, Default)]
+pub struct UniqueIndexes {
+    collections: HashMap<String, Vec<Vec<String>>>,
+}
+
+impl UniqueIndexes {
+    pub fn add(&mut self, collection: &str, fields: &[&str]) {
+        let fields: Vec<String> = fields.iter().map(|f| (*f).to_owned()).collect();
+        let indexes = self.collections.entry(collection.to_owned()).or_default();
+        if !indexes.contains(&fields) {
+            indexes.push(fields);
+        }
+    }
+
+    pub fn get(&self, collection: &str) -> &[Vec<String>] {
+        self.collections.get(collection).map_or(&[], Vec::as_slice)
+    }
+
+    // fails if another document has the same values for the fields of an index,
+    // missing fields count as null like in mongodb
+    pub fn check<'a>(
+        &self,
+        collection: &str,
+        candidate: &Document,
+        others: impl Iterator<Item = &'a Document> + Clone,
+    ) -> Result<(), DbError> {
+        let Some(indexes) = self.collections.get(collection) else {
+            return Ok(());
+        };
+        let id = candidate.get("_id");
+        for fields in indexes {
+            let key = |d: &Document| -> Vec<Bson> {
+                fields
+                    .iter()
+                    .map(|f| get_path(d, f).cloned().unwrap_or(Bson::Null))
+                    .collect()
+            };
+            let candidate_key = key(candidate);
+            if others
+                .clone()
+                .any(|other| other.get("_id") != id && key(other) == candidate_key)
+            {
+                return Err(DbError::AlreadyExists);
+            }
+        }
+        Ok(())
+    }
+}
+
+// ttl indexes of the backends without a query engine: like in mongodb, a document expires
+// once its field is a date older than the delay, documents without a date are kept
+#[derive(Debug, Default)]
+pub struct TtlIndexes {
+    collections: HashMap<String, (String, Duration)>,
+}
+
+impl TtlIndexes {
+    pub fn add(&mut self, collection: &str, field: &str, expire_after: Duration) {
+        self.collections
+            .insert(collection.to_owned(), (field.to_owned(), expire_after));
+    }
+
+    pub fn collections(&self) -> impl Iterator<Item = &str> {
+        self.collections.keys().map(String::as_str)
+    }
+
+    pub fn contains(&self, collection: &str) -> bool {
+        self.collections.contains_key(collection)
+    }
+
+    pub fn expired(&self, collection: &str, document: &Document, now: bson::DateTime) -> bool {
+        let Some((field, expire_after)) = self.collections.get(collection) else {
+            return false;
+        };
+        let Some(Bson::DateTime(at)) = get_path(document, field) else {
+            return false;
+        };
+        let expire_after = i64::try_from(expire_after.as_millis()).unwrap_or(i64::MAX);
+        at.timestamp_millis().saturating_add(expire_after) <= now.timestamp_millis()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bson::doc;
+
+    #[test]
+    fn test_matches() {
+        let now = bson::DateTime::now();
+        let document = doc! {
+            "user_id": "10",
+            "count": 3,
+            "at": now,
+            "votes": {"12": 1},
+            "args": null,
+        };
+
+        assert!(matches(&document, &doc! {}));
+        assert!(matches(&document, &doc! {"user_id": "10", "args": null}));
+        assert!(!matches(&document, &doc! {"user_id": "11"}));
+        assert!(!matches(&document, &doc! {"missing": "10"}));
+        assert!(matches(&document, &doc! {"votes.12": 1}));
+        assert!(matches(
+            &document,
+            &doc! {"count": {"$gte": 3_i64, "$lt": 3.5}}
+        ));
+        assert!(!matches(&document, &doc! {"count": {"$gt": 3}}));
+        assert!(matches(&document, &doc! {"at": {"$lte": now}}));
+        assert!(matches(&document, &doc! {"user_id": {"$in": ["9", "10"]}}));
+        assert!(matches(&document, &doc! {"user_id": {"$ne": "9"}}));
+        assert!(matches(&document, &doc! {"missing": {"$ne": "9"}}));
+        assert!(matches(&document, &doc! {"votes.12": {"$exists": true}}));
+        assert!(matches(&document, &doc! {"votes.13": {"$exists": false}}));
+    }
+
+    #[test]
+    fn test_apply_update() {
+        let id = ObjectId::new();
+        let mut document = doc! {"_id": id, "games": 1, "votes": {}};
+        apply_update(
+            &mut document,
+            &doc! {"$set": {"votes.12": 2, "rating": 1016.0}, "$inc": {"games": 1}},
+        )
+        .unwrap();
+        assert_eq!(
+            document,
+            doc! {"_id": id, "games": 2, "votes": {"12": 2}, "rating": 1016.0}
+        );
+
+        apply_update(&mut document, &doc! {"$unset": {"votes.12": ""}}).unwrap();
+        assert_eq!(document.get_document("votes").unwrap(), &doc! {});
+        apply_update(&mut document, &doc! {"name": "remplacé"}).unwrap();
+        assert_eq!(document, doc! {"name": "remplacé", "_id": id});
+        assert!(apply_update(&mut document, &doc! {"$push": {"a": 1}}).is_err());
+
+        let created = upserted(
+            &doc! {"user_id": "10", "at": {"$lte": 3}},
+            &doc! {"$inc": {"games": 1}},
+        )
+        .unwrap();
+        assert_eq!(created.get_str("user_id").unwrap(), "10");
+        assert_eq!(created.get_i32("games").unwrap(), 1);
+        assert!(!created.contains_key("at"));
+        assert!(created.contains_key("_id"));
+    }
+
+    #[test]
+    fn test_unique_indexes() {
+        let mut indexes = UniqueIndexes::default();
+        indexes.add("macros", &["user_id", "name"]);
+        let stored = [
+            doc! {"_id": 1, "user_id": "10", "name": "init"},
+            doc! {"_id": 2, "user_id": "11", "name": "init"},
+        ];
+        let check = |candidate| indexes.check("macros", &candidate, stored.iter());
+
+        assert!(check(doc! {"_id": 3, "user_id": "10", "name": "d6"}).is_ok());
+        assert!(check(doc! {"_id": 3, "user_id": "10", "name": "init"}).is_err());
+        // a document doesn't conflict with its previous version
+        assert!(check(doc! {"_id": 1, "user_id": "10", "name": "init"}).is_ok());
+        assert!(indexes.check("quotes", &stored[0], stored.iter()).is_ok());
+    }
+
+    #[test]
+    fn test_ttl_indexes() {
+        let mut indexes = TtlIndexes::default();
+        indexes.add("temp_macros", "created_at", Duration::from_secs(60));
+        let now = bson::DateTime::now();
+        let old = bson::DateTime::from_millis(now.timestamp_millis() - 60_000);
+        let expired = |document| indexes.expired("temp_macros", &document, now);
+
+        assert!(expired(doc! {"created_at": old}));
+        assert!(!expired(doc! {"created_at": now}));
+        assert!(!expired(doc! {"created_at": "hier"}));
+        assert!(!expired(doc! {}));
+        assert!(!indexes.expired("macros", &doc! {"created_at": old}, now));
+    }
+}
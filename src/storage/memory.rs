@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bson::{oid::ObjectId, Bson, Document};
+use futures::future::{self, BoxFuture};
+use futures::FutureExt;
+
+use super::matcher::{apply_update, matches, upserted, TtlIndexes, UniqueIndexes};
+use super::Storage;
+use crate::db::DbError;
+
+#[derive(Debug, Default)]
+struct Collections {
+    documents: HashMap<String, Vec<Document>>,
+    indexes: UniqueIndexes,
+    ttl: TtlIndexes,
+}
+
+impl Collections {
+    fn sweep(&mut self) {
+        let now = bson::DateTime::now();
+        for collection in self.ttl.collections() {
+            if let Some(documents) = self.documents.get_mut(collection) {
+                documents.retain(|d| !self.ttl.expired(collection, d, now));
+            }
+        }
+    }
+
+    fn insert(&mut self, collection: &str, mut document: Document) -> Result<Bson, DbError> {
+        let id = document
+            .entry("_id".to_owned())
+            .or_insert_with(|| ObjectId::new().into())
+            .clone();
+        let documents = self.documents.entry(collection.to_owned()).or_default();
+        self.indexes
+            .check(collection, &document, documents.iter())?;
+        documents.push(document);
+        Ok(id)
+    }
+
+    // replaces the first matching document and returns its previous version
+    fn replace(
+        &mut self,
+        collection: &str,
+        filter: &Document,
+        update: impl FnOnce(&mut Document) -> Result<(), DbError>,
+    ) -> Result<Option<Document>, DbError> {
+        let documents = self.documents.entry(collection.to_owned()).or_default();
+        let Some(i) = documents.iter().position(|d| matches(d, filter)) else {
+            return Ok(None);
+        };
+        let mut document = documents[i].clone();
+        update(&mut document)?;
+        self.indexes
+            .check(collection, &document, documents.iter())?;
+        Ok(Some(std::mem::replace(&mut documents[i], document)))
+    }
+}
+
+// collections kept in memory, for tests and for running the bot without a database
+#[derive(Debug, Default)]
+pub struct Memory {
+    collections: Mutex<Collections>,
+}
+
+impl Memory {
+    // every operation holds the lock from start to end, so they are all atomic,
+    // and starts by removing the expired documents
+    fn with<T>(
+        &self,
+        f: impl FnOnce(&mut Collections) -> Result<T, DbError>,
+    ) -> Result<T, DbError> {
+        let mut collections = self
+            .collections
+            .lock()
+            .map_err(|_| DbError::internal("memory storage poisoned"))?;
+        collections.sweep();
+        f(&mut collections)
+    }
+
+    fn matching(&self, collection: &str, filter: &Document) -> Result<Vec<Document>, DbError> {
+        self.with(|c| {
+            Ok(c.documents
+                .get(collection)
+                .map(|documents| {
+                    documents
+                        .iter()
+                        .filter(|d| matches(d, filter))
+                        .cloned()
+                        .collect()
+                })
+                .unwrap_or_default())
+        })
+    }
+}
+
+impl Storage for Memory {
+    fn find<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<Vec<Document>, DbError>> {
+        future::ready(self.matching(collection, &filter)).boxed()
+    }
+
+    fn find_one<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<Option<Document>, DbError>> {
+        future::ready(
+            self.matching(collection, &filter)
+                .map(|documents| documents.into_iter().next()),
+        )
+        .boxed()
+    }
+
+    fn insert_one<'a>(
+        &'a self,
+        collection: &'a str,
+        document: Document,
+    ) -> BoxFuture<'a, Result<Bson, DbError>> {
+        future::ready(self.with(|c| c.insert(collection, document))).boxed()
+    }
+
+    fn insert_if_absent<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+        document: Document,
+    ) -> BoxFuture<'a, Result<Option<Bson>, DbError>> {
+        future::ready(self.with(|c| {
+            let exists = c
+                .documents
+                .get(collection)
+                .is_some_and(|documents| documents.iter().any(|d| matches(d, &filter)));
+            if exists {
+                Ok(None)
+            } else {
+                c.insert(collection, document).map(Some)
+            }
+        }))
+        .boxed()
+    }
+
+    fn update_one<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+        update: Document,
+        upsert: bool,
+    ) -> BoxFuture<'a, Result<u64, DbError>> {
+        future::ready(self.with(|c| {
+            if c.replace(collection, &filter, |d| apply_update(d, &update))?
+                .is_some()
+            {
+                Ok(1)
+            } else if upsert {
+                c.insert(collection, upserted(&filter, &update)?)?;
+                Ok(1)
+            } else {
+                Ok(0)
+            }
+        }))
+        .boxed()
+    }
+
+    fn find_one_and_replace<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+        replacement: Document,
+        upsert: bool,
+    ) -> BoxFuture<'a, Result<Option<Document>, DbError>> {
+        future::ready(self.with(|c| {
+            let previous = c.replace(collection, &filter, |d| apply_update(d, &replacement))?;
+            if previous.is_none() && upsert {
+                c.insert(collection, upserted(&filter, &replacement)?)?;
+            }
+            Ok(previous)
+        }))
+        .boxed()
+    }
+
+    fn delete_one<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<u64, DbError>> {
+        future::ready(self.with(|c| {
+            let Some(documents) = c.documents.get_mut(collection) else {
+                return Ok(0);
+            };
+            Ok(documents
+                .iter()
+                .position(|d| matches(d, &filter))
+                .map_or(0, |i| {
+                    documents.remove(i);
+                    1
+                }))
+        }))
+        .boxed()
+    }
+
+    fn delete_many<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<u64, DbError>> {
+        future::ready(self.with(|c| {
+            let Some(documents) = c.documents.get_mut(collection) else {
+                return Ok(0);
+            };
+            let before = documents.len();
+            documents.retain(|d| !matches(d, &filter));
+            Ok((before - documents.len()) as u64)
+        }))
+        .boxed()
+    }
+
+    fn create_unique_index<'a>(
+        &'a self,
+        collection: &'a str,
+        fields: &'a [&'a str],
+    ) -> BoxFuture<'a, Result<(), DbError>> {
+        future::ready(self.with(|c| {
+            c.indexes.add(collection, fields);
+            let documents = c.documents.get(collection).map_or(&[][..], Vec::as_slice);
+            documents
+                .iter()
+                .try_for_each(|d| c.indexes.check(collection, d, documents.iter()))
+        }))
+        .boxed()
+    }
+
+    fn create_ttl_index<'a>(
+        &'a self,
+        collection: &'a str,
+        field: &'a str,
+        expire_after: Duration,
+    ) -> BoxFuture<'a, Result<(), DbError>> {
+        future::ready(self.with(|c| {
+            c.ttl.add(collection, field, expire_after);
+            Ok(())
+        }))
+        .boxed()
+    }
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use bson::{Bson, Document};
+use futures::future::BoxFuture;
+
+use crate::db::DbError;
+
+pub mod matcher;
+pub mod memory;
+pub mod mongo;
+pub mod sqlite;
+
+pub use memory::Memory;
+pub use mongo::Mongo;
+pub use sqlite::Sqlite;
+
+// raw documents of a backend, the typed helpers of `db` are built on top of it
+pub trait Storage: std::fmt::Debug + Send + Sync {
+    fn find<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<Vec<Document>, DbError>>;
+
+    fn find_one<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<Option<Document>, DbError>>;
+
+    // returns the _id of the document, created if it has none
+    fn insert_one<'a>(
+        &'a self,
+        collection: &'a str,
+        document: Document,
+    ) -> BoxFuture<'a, Result<Bson, DbError>>;
+
+    // inserts the document only if none matches the filter, in one atomic operation,
+    // returns the _id of the inserted document
+    fn insert_if_absent<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+        document: Document,
+    ) -> BoxFuture<'a, Result<Option<Bson>, DbError>>;
+
+    // returns the number of matched or created documents
+    fn update_one<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+        update: Document,
+        upsert: bool,
+    ) -> BoxFuture<'a, Result<u64, DbError>>;
+
+    // replaces the first matching document atomically and returns its previous version
+    fn find_one_and_replace<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+        replacement: Document,
+        upsert: bool,
+    ) -> BoxFuture<'a, Result<Option<Document>, DbError>>;
+
+    // returns the number of deleted documents
+    fn delete_one<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<u64, DbError>>;
+
+    fn delete_many<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<u64, DbError>>;
+
+    // fails if stored documents already share values for these fields
+    fn create_unique_index<'a>(
+        &'a self,
+        collection: &'a str,
+        fields: &'a [&'a str],
+    ) -> BoxFuture<'a, Result<(), DbError>>;
+
+    // documents are deleted once the date in the field is older than the delay,
+    // not right away in mongodb which checks every minute
+    fn create_ttl_index<'a>(
+        &'a self,
+        collection: &'a str,
+        field: &'a str,
+        expire_after: Duration,
+    ) -> BoxFuture<'a, Result<(), DbError>>;
+}
@@ -0,0 +1,191 @@
+use std::time::Duration;
+
+use bson::{doc, Bson, Document};
+use futures::future::BoxFuture;
+use futures::{FutureExt, TryStreamExt};
+use mongodb::options::{ClientOptions, IndexOptions, ReturnDocument};
+use mongodb::{Client, Collection, Database, IndexModel};
+
+use super::Storage;
+use crate::db::DbError;
+
+#[derive(Debug)]
+pub struct Mongo {
+    database: Database,
+}
+
+impl Mongo {
+    pub async fn connect(uri: &str) -> Result<Self, DbError> {
+        let options = ClientOptions::parse(uri).await?;
+        let client = Client::with_options(options)?;
+        Ok(Self {
+            database: client.database("teamy"),
+        })
+    }
+
+    fn coll(&self, collection: &str) -> Collection<Document> {
+        self.database.collection(collection)
+    }
+}
+
+impl Storage for Mongo {
+    fn find<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<Vec<Document>, DbError>> {
+        async move {
+            self.coll(collection)
+                .find(filter)
+                .await?
+                .try_collect()
+                .await
+                .map_err(DbError::from)
+        }
+        .boxed()
+    }
+
+    fn find_one<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<Option<Document>, DbError>> {
+        async move { Ok(self.coll(collection).find_one(filter).await?) }.boxed()
+    }
+
+    fn insert_one<'a>(
+        &'a self,
+        collection: &'a str,
+        document: Document,
+    ) -> BoxFuture<'a, Result<Bson, DbError>> {
+        async move {
+            Ok(self
+                .coll(collection)
+                .insert_one(document)
+                .await?
+                .inserted_id)
+        }
+        .boxed()
+    }
+
+    fn insert_if_absent<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+        document: Document,
+    ) -> BoxFuture<'a, Result<Option<Bson>, DbError>> {
+        async move {
+            let result = self
+                .coll(collection)
+                .update_one(filter, doc! {"$setOnInsert": document})
+                .upsert(true)
+                .await?;
+            Ok(result.upserted_id)
+        }
+        .boxed()
+    }
+
+    fn update_one<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+        update: Document,
+        upsert: bool,
+    ) -> BoxFuture<'a, Result<u64, DbError>> {
+        async move {
+            let coll = self.coll(collection);
+            let result = if update.keys().all(|k| k.starts_with('$')) {
+                coll.update_one(filter, update).upsert(upsert).await?
+            } else {
+                coll.replace_one(filter, update).upsert(upsert).await?
+            };
+            Ok(result.matched_count + u64::from(result.upserted_id.is_some()))
+        }
+        .boxed()
+    }
+
+    fn find_one_and_replace<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+        replacement: Document,
+        upsert: bool,
+    ) -> BoxFuture<'a, Result<Option<Document>, DbError>> {
+        async move {
+            Ok(self
+                .coll(collection)
+                .find_one_and_replace(filter, replacement)
+                .upsert(upsert)
+                .return_document(ReturnDocument::Before)
+                .await?)
+        }
+        .boxed()
+    }
+
+    fn delete_one<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<u64, DbError>> {
+        async move {
+            Ok(self
+                .coll(collection)
+                .delete_one(filter)
+                .await?
+                .deleted_count)
+        }
+        .boxed()
+    }
+
+    fn delete_many<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<u64, DbError>> {
+        async move {
+            Ok(self
+                .coll(collection)
+                .delete_many(filter)
+                .await?
+                .deleted_count)
+        }
+        .boxed()
+    }
+
+    fn create_unique_index<'a>(
+        &'a self,
+        collection: &'a str,
+        fields: &'a [&'a str],
+    ) -> BoxFuture<'a, Result<(), DbError>> {
+        async move {
+            let keys: Document = fields
+                .iter()
+                .map(|f| ((*f).to_owned(), Bson::Int32(1)))
+                .collect();
+            let index = IndexModel::builder()
+                .keys(keys)
+                .options(IndexOptions::builder().unique(true).build())
+                .build();
+            self.coll(collection).create_index(index).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn create_ttl_index<'a>(
+        &'a self,
+        collection: &'a str,
+        field: &'a str,
+        expire_after: Duration,
+    ) -> BoxFuture<'a, Result<(), DbError>> {
+        async move {
+            let index = IndexModel::builder()
+                .keys(doc! {field: 1})
+                .options(IndexOptions::builder().expire_after(expire_after).build())
+                .build();
+            self.coll(collection).create_index(index).await?;
+            Ok(())
+        }
+        .boxed()
+    }
+}
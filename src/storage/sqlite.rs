@@ -0,0 +1,532 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bson::{oid::ObjectId, Bson, Document};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use rusqlite::{params, params_from_iter, Connection};
+
+use super::matcher::{apply_update, matches, upserted, TtlIndexes, UniqueIndexes};
+use super::Storage;
+use crate::db::DbError;
+
+// fields most filters start with, copied into indexed columns
+const KEYS: [&str; 2] = ["user_id", "guild_id"];
+
+type Fill = fn(&Connection) -> Result<(), DbError>;
+
+// each step brings the schema to the next user_version, never edit a released one,
+// the function fills what sql can't compute from the bson bodies
+const MIGRATIONS: &[(&str, Option<Fill>)] = &[
+    (
+        "
+    CREATE TABLE documents (
+        id INTEGER PRIMARY KEY,
+        collection TEXT NOT NULL,
+        body BLOB NOT NULL
+    );
+    CREATE INDEX documents_collection ON documents (collection);
+",
+        None,
+    ),
+    (
+        "
+    ALTER TABLE documents ADD COLUMN user_id TEXT;
+    ALTER TABLE documents ADD COLUMN guild_id TEXT;
+    CREATE INDEX documents_user_id ON documents (collection, user_id);
+    CREATE INDEX documents_guild_id ON documents (collection, guild_id);
+",
+        Some(fill_keys),
+    ),
+];
+
+fn migrate(connection: &mut Connection) -> Result<(), DbError> {
+    let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (i, (migration, fill)) in MIGRATIONS.iter().enumerate().skip(version) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        if let Some(fill) = fill {
+            fill(&transaction)?;
+        }
+        transaction.pragma_update(None, "user_version", i + 1)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+fn to_bytes(document: &Document) -> Result<Vec<u8>, DbError> {
+    let mut bytes = Vec::new();
+    document.to_writer(&mut bytes)?;
+    Ok(bytes)
+}
+
+// values of the indexed columns, only strings are indexed
+fn keys(document: &Document) -> [Option<&str>; 2] {
+    KEYS.map(|key| document.get_str(key).ok())
+}
+
+fn fill_keys(connection: &Connection) -> Result<(), DbError> {
+    let rows: Vec<(i64, Vec<u8>)> = connection
+        .prepare("SELECT id, body FROM documents")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+    let mut update =
+        connection.prepare("UPDATE documents SET user_id = ?1, guild_id = ?2 WHERE id = ?3")?;
+    for (id, body) in rows {
+        let document = Document::from_reader(body.as_slice())?;
+        let [user_id, guild_id] = keys(&document);
+        update.execute(params![user_id, guild_id, id])?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Inner {
+    connection: Connection,
+    indexes: UniqueIndexes,
+    ttl: TtlIndexes,
+}
+
+impl Inner {
+    fn sweep(&self, collection: &str) -> Result<(), DbError> {
+        if !self.ttl.contains(collection) {
+            return Ok(());
+        }
+        let now = bson::DateTime::now();
+        let expired: Vec<i64> = self
+            .matching(collection, &Document::new())?
+            .iter()
+            .filter(|(_, d)| self.ttl.expired(collection, d, now))
+            .map(|(id, _)| *id)
+            .collect();
+        self.remove(&expired)?;
+        Ok(())
+    }
+
+    // documents of the collection matching the filter, with their row id,
+    // the indexed columns narrow down the rows decoded for the matcher
+    fn matching(
+        &self,
+        collection: &str,
+        filter: &Document,
+    ) -> Result<Vec<(i64, Document)>, DbError> {
+        let mut sql = String::from("SELECT id, body FROM documents WHERE collection = ?1");
+        let mut values = vec![collection];
+        for key in KEYS {
+            if let Some(Bson::String(value)) = filter.get(key) {
+                values.push(value);
+                sql.push_str(&format!(" AND {key} = ?{}", values.len()));
+            }
+        }
+        sql.push_str(" ORDER BY id");
+        let mut statement = self.connection.prepare_cached(&sql)?;
+        let rows = statement.query_map(params_from_iter(values), |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?))
+        })?;
+
+        let mut documents = Vec::new();
+        for row in rows {
+            let (id, body) = row?;
+            let document = Document::from_reader(body.as_slice())?;
+            if matches(&document, filter) {
+                documents.push((id, document));
+            }
+        }
+        Ok(documents)
+    }
+
+    // a key that is part of every index of the collection must be equal for a duplicate,
+    // so only the documents sharing it are read
+    fn check_unique(&self, collection: &str, document: &Document) -> Result<(), DbError> {
+        let indexes = self.indexes.get(collection);
+        if indexes.is_empty() {
+            return Ok(());
+        }
+        let mut filter = Document::new();
+        for key in KEYS {
+            if let Ok(value) = document.get_str(key) {
+                if indexes.iter().all(|fields| fields.iter().any(|f| f == key)) {
+                    filter.insert(key, value);
+                }
+            }
+        }
+        let stored = self.matching(collection, &filter)?;
+        self.indexes
+            .check(collection, document, stored.iter().map(|(_, d)| d))
+    }
+
+    fn insert(&self, collection: &str, mut document: Document) -> Result<Bson, DbError> {
+        let id = document
+            .entry("_id".to_owned())
+            .or_insert_with(|| ObjectId::new().into())
+            .clone();
+        self.check_unique(collection, &document)?;
+        let [user_id, guild_id] = keys(&document);
+        self.connection.execute(
+            "INSERT INTO documents (collection, body, user_id, guild_id) VALUES (?1, ?2, ?3, ?4)",
+            params![collection, to_bytes(&document)?, user_id, guild_id],
+        )?;
+        Ok(id)
+    }
+
+    // replaces the first matching document and returns its previous version
+    fn replace(
+        &self,
+        collection: &str,
+        filter: &Document,
+        update: &Document,
+    ) -> Result<Option<Document>, DbError> {
+        let Some((id, previous)) = self.matching(collection, filter)?.into_iter().next() else {
+            return Ok(None);
+        };
+        let mut document = previous.clone();
+        apply_update(&mut document, update)?;
+        self.check_unique(collection, &document)?;
+        let [user_id, guild_id] = keys(&document);
+        self.connection.execute(
+            "UPDATE documents SET body = ?1, user_id = ?2, guild_id = ?3 WHERE id = ?4",
+            params![to_bytes(&document)?, user_id, guild_id, id],
+        )?;
+        Ok(Some(previous))
+    }
+
+    fn remove(&self, ids: &[i64]) -> Result<u64, DbError> {
+        let mut statement = self
+            .connection
+            .prepare_cached("DELETE FROM documents WHERE id = ?1")?;
+        for id in ids {
+            statement.execute([id])?;
+        }
+        Ok(ids.len() as u64)
+    }
+}
+
+// documents stored as bson in a single table, filtered with the same matcher as the memory storage
+#[derive(Debug)]
+pub struct Sqlite {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Sqlite {
+    // creates the file if needed and applies the missing migrations
+    pub fn open(path: &str) -> Result<Self, DbError> {
+        let mut connection = Connection::open(path)?;
+        migrate(&mut connection)?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(Inner {
+                connection,
+                indexes: UniqueIndexes::default(),
+                ttl: TtlIndexes::default(),
+            })),
+        })
+    }
+
+    // the connection is used by one operation at a time, in a transaction, so they are all
+    // atomic, and each starts by removing the expired documents of its collection;
+    // sqlite blocks, so this runs on the blocking threads instead of the async workers
+    fn with<T: Send + 'static>(
+        &self,
+        collection: &str,
+        f: impl FnOnce(&mut Inner, &str) -> Result<T, DbError> + Send + 'static,
+    ) -> BoxFuture<'static, Result<T, DbError>> {
+        let inner = Arc::clone(&self.inner);
+        let collection = collection.to_owned();
+        tokio::task::spawn_blocking(move || {
+            let mut inner = inner
+                .lock()
+                .map_err(|_| DbError::internal("sqlite connection poisoned"))?;
+            inner.connection.execute_batch("BEGIN")?;
+            let result = inner
+                .sweep(&collection)
+                .and_then(|()| f(&mut inner, &collection))
+                .and_then(|value| {
+                    inner.connection.execute_batch("COMMIT")?;
+                    Ok(value)
+                });
+            // a failed commit leaves the transaction open too, every later BEGIN would fail
+            if result.is_err() && !inner.connection.is_autocommit() {
+                inner.connection.execute_batch("ROLLBACK")?;
+            }
+            result
+        })
+        .map(|joined| joined.unwrap_or_else(|e| Err(DbError::internal(e.to_string()))))
+        .boxed()
+    }
+}
+
+impl Storage for Sqlite {
+    fn find<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<Vec<Document>, DbError>> {
+        self.with(collection, move |inner, collection| {
+            Ok(inner
+                .matching(collection, &filter)?
+                .into_iter()
+                .map(|(_, document)| document)
+                .collect())
+        })
+    }
+
+    fn find_one<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<Option<Document>, DbError>> {
+        self.with(collection, move |inner, collection| {
+            Ok(inner
+                .matching(collection, &filter)?
+                .into_iter()
+                .next()
+                .map(|(_, document)| document))
+        })
+    }
+
+    fn insert_one<'a>(
+        &'a self,
+        collection: &'a str,
+        document: Document,
+    ) -> BoxFuture<'a, Result<Bson, DbError>> {
+        self.with(collection, move |inner, collection| {
+            inner.insert(collection, document)
+        })
+    }
+
+    fn insert_if_absent<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+        document: Document,
+    ) -> BoxFuture<'a, Result<Option<Bson>, DbError>> {
+        self.with(collection, move |inner, collection| {
+            if inner.matching(collection, &filter)?.is_empty() {
+                inner.insert(collection, document).map(Some)
+            } else {
+                Ok(None)
+            }
+        })
+    }
+
+    fn update_one<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+        update: Document,
+        upsert: bool,
+    ) -> BoxFuture<'a, Result<u64, DbError>> {
+        self.with(collection, move |inner, collection| {
+            if inner.replace(collection, &filter, &update)?.is_some() {
+                Ok(1)
+            } else if upsert {
+                inner.insert(collection, upserted(&filter, &update)?)?;
+                Ok(1)
+            } else {
+                Ok(0)
+            }
+        })
+    }
+
+    fn find_one_and_replace<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+        replacement: Document,
+        upsert: bool,
+    ) -> BoxFuture<'a, Result<Option<Document>, DbError>> {
+        self.with(collection, move |inner, collection| {
+            let previous = inner.replace(collection, &filter, &replacement)?;
+            if previous.is_none() && upsert {
+                inner.insert(collection, upserted(&filter, &replacement)?)?;
+            }
+            Ok(previous)
+        })
+    }
+
+    fn delete_one<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<u64, DbError>> {
+        self.with(collection, move |inner, collection| {
+            let ids: Vec<i64> = inner
+                .matching(collection, &filter)?
+                .iter()
+                .take(1)
+                .map(|(id, _)| *id)
+                .collect();
+            inner.remove(&ids)
+        })
+    }
+
+    fn delete_many<'a>(
+        &'a self,
+        collection: &'a str,
+        filter: Document,
+    ) -> BoxFuture<'a, Result<u64, DbError>> {
+        self.with(collection, move |inner, collection| {
+            let ids: Vec<i64> = inner
+                .matching(collection, &filter)?
+                .iter()
+                .map(|(id, _)| *id)
+                .collect();
+            inner.remove(&ids)
+        })
+    }
+
+    // kept in memory and created again at every startup
+    fn create_unique_index<'a>(
+        &'a self,
+        collection: &'a str,
+        fields: &'a [&'a str],
+    ) -> BoxFuture<'a, Result<(), DbError>> {
+        let fields: Vec<String> = fields.iter().map(|f| (*f).to_owned()).collect();
+        self.with(collection, move |inner, collection| {
+            let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+            inner.indexes.add(collection, &fields);
+            let stored = inner.matching(collection, &Document::new())?;
+            stored.iter().try_for_each(|(_, d)| {
+                inner
+                    .indexes
+                    .check(collection, d, stored.iter().map(|(_, d)| d))
+            })
+        })
+    }
+
+    // kept in memory too
+    fn create_ttl_index<'a>(
+        &'a self,
+        collection: &'a str,
+        field: &'a str,
+        expire_after: Duration,
+    ) -> BoxFuture<'a, Result<(), DbError>> {
+        let field = field.to_owned();
+        self.with(collection, move |inner, collection| {
+            inner.ttl.add(collection, &field, expire_after);
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bson::doc;
+
+    #[tokio::test]
+    async fn test_sqlite() {
+        let storage = Sqlite::open(":memory:").unwrap();
+        let now = bson::DateTime::now();
+        storage
+            .insert_one(
+                "polls",
+                doc! {"closed": false, "closes_at": now, "votes": {}},
+            )
+            .await
+            .unwrap();
+        storage
+            .insert_one("polls", doc! {"closed": true, "votes": {}})
+            .await
+            .unwrap();
+        assert!(storage.find("quotes", doc! {}).await.unwrap().is_empty());
+
+        let due = doc! {"closed": false, "closes_at": {"$lte": now}};
+        assert_eq!(storage.find("polls", due.clone()).await.unwrap().len(), 1);
+        storage
+            .update_one("polls", due, doc! {"$set": {"votes.10": 1}}, false)
+            .await
+            .unwrap();
+        let poll = storage
+            .find_one("polls", doc! {"votes.10": 1})
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(poll.get_datetime("closes_at").unwrap(), &now);
+
+        storage
+            .update_one(
+                "ratings",
+                doc! {"user_id": "10"},
+                doc! {"$inc": {"games": 1}},
+                true,
+            )
+            .await
+            .unwrap();
+        assert_eq!(storage.find("ratings", doc! {}).await.unwrap().len(), 1);
+        assert_eq!(storage.delete_many("polls", doc! {}).await.unwrap(), 2);
+
+        // migrations already applied are skipped
+        let mut connection = Arc::try_unwrap(storage.inner)
+            .unwrap()
+            .into_inner()
+            .unwrap()
+            .connection;
+        migrate(&mut connection).unwrap();
+        let version: usize = connection
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+    }
+
+    #[tokio::test]
+    async fn test_keys() {
+        // a document stored before the indexed columns existed
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(MIGRATIONS[0].0).unwrap();
+        connection.pragma_update(None, "user_version", 1).unwrap();
+        let old = doc! {"_id": 1, "user_id": "10", "name": "init"};
+        connection
+            .execute(
+                "INSERT INTO documents (collection, body) VALUES ('macros', ?1)",
+                [to_bytes(&old).unwrap()],
+            )
+            .unwrap();
+        migrate(&mut connection).unwrap();
+        let user_id: String = connection
+            .query_row("SELECT user_id FROM documents", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(user_id, "10");
+
+        let storage = Sqlite {
+            inner: Arc::new(Mutex::new(Inner {
+                connection,
+                indexes: UniqueIndexes::default(),
+                ttl: TtlIndexes::default(),
+            })),
+        };
+        storage
+            .create_unique_index("macros", &["user_id", "name"])
+            .await
+            .unwrap();
+        storage
+            .insert_one("macros", doc! {"user_id": "11", "name": "init"})
+            .await
+            .unwrap();
+        let duplicate = doc! {"user_id": "10", "name": "init"};
+        assert!(storage.insert_one("macros", duplicate).await.is_err());
+        let found = storage
+            .find("macros", doc! {"user_id": "10"})
+            .await
+            .unwrap();
+        assert_eq!(found, vec![old]);
+
+        // an update creating a duplicate is rejected
+        storage
+            .update_one(
+                "macros",
+                doc! {"user_id": "11"},
+                doc! {"$set": {"user_id": "10"}},
+                false,
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            storage
+                .find("macros", doc! {"user_id": "11"})
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::utils;
+
+// values available to the placeholders of a template
+#[derive(Debug, Default)]
+pub struct Values<'a> {
+    pub user: &'a str,
+    pub channel: &'a str,
+    pub message: &'a str,
+    // emoji name -> emoji, names not found are imagined
+    pub emojis: HashMap<String, String>,
+}
+
+// renders {user}, {channel}, {message}, {emoji:name}, {random:a|b} and {nerd:text},
+// "{{" and "}}" are literal braces and unknown placeholders are kept as is
+pub fn render<R: Rng>(rng: &mut R, template: &str, values: &Values) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        let brace = &rest[i..=i];
+        let after = &rest[i + 1..];
+        if after.starts_with(brace) {
+            out.push_str(brace);
+            rest = &after[1..];
+        } else if brace == "{" {
+            let Some(end) = closing(after) else {
+                out.push_str(&rest[i..]);
+                return out;
+            };
+            out.push_str(&placeholder(rng, &after[..end], values));
+            rest = &after[end + 1..];
+        } else {
+            out.push_str(brace);
+            rest = after;
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn placeholder<R: Rng>(rng: &mut R, inner: &str, values: &Values) -> String {
+    let (name, arg) = inner.split_once(':').unwrap_or((inner, ""));
+    match name {
+        "user" => values.user.to_owned(),
+        "channel" => values.channel.to_owned(),
+        "message" => values.message.to_owned(),
+        "emoji" => values
+            .emojis
+            .get(arg)
+            .cloned()
+            .unwrap_or_else(|| imagine(arg)),
+        "random" => {
+            let options = split_options(arg);
+            let choice = options.choose(rng).copied().unwrap_or("");
+            render(rng, choice, values)
+        }
+        "nerd" => utils::nerdify(&render(rng, arg, values)),
+        _ => format!("{{{inner}}}"),
+    }
+}
+
+pub fn imagine(emoji: &str) -> String {
+    format!("<veuillez imaginer l'emoji \"{emoji}\">")
+}
+
+// index of the brace closing a placeholder, taking nested ones into account
+fn closing(s: &str) -> Option<usize> {
+    let mut depth = 0_usize;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+// splits on "|" outside of nested placeholders
+pub fn split_options(s: &str) -> Vec<&str> {
+    let mut options = Vec::new();
+    let mut depth = 0_usize;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            '|' if depth == 0 => {
+                options.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    options.push(&s[start..]);
+    options
+}
+
+// names of the emojis a template may need
+pub fn emoji_names(template: &str) -> Vec<String> {
+    template
+        .match_indices("{emoji:")
+        .filter_map(|(i, tag)| {
+            let rest = &template[i + tag.len()..];
+            rest.find('}').map(|end| rest[..end].to_owned())
+        })
+        .collect()
+}
+
+// makes text usable as a template that renders to itself
+pub fn escape(text: &str) -> String {
+    text.replace('{', "{{").replace('}', "}}")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_render() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let values = Values {
+            user: "Jean",
+            channel: "<#1>",
+            message: "bad bot",
+            emojis: HashMap::from([("saucisse".to_owned(), "<:saucisse:2>".to_owned())]),
+        };
+        let mut render = |template| render(&mut rng, template, &values);
+
+        assert_eq!(render("Salut {user} !"), "Salut Jean !");
+        assert_eq!(render("dans {channel}"), "dans <#1>");
+        assert_eq!(render("{emoji:saucisse}"), "<:saucisse:2>");
+        assert_eq!(render("{emoji:bedge}"), imagine("bedge"));
+        assert_eq!(render("{nerd:{message}}"), "bAd bOt");
+        assert_eq!(render("{{user}} {inconnu}"), "{user} {inconnu}");
+        assert_eq!(render("pas fini {user"), "pas fini {user");
+        let escaped = escape("{user} }");
+        assert_eq!(render(&escaped), "{user} }");
+        assert!(["feur", "quoicoubeh"].contains(&render("{random:feur|quoicoubeh}").as_str()));
+        assert!(
+            ["a Jean", "bAd bOt"].contains(&render("{random:a {user}|{nerd:{message}}}").as_str())
+        );
+    }
+
+    #[test]
+    fn test_emoji_names() {
+        assert_eq!(
+            emoji_names("{emoji:saucisse} {random:{emoji:bedge}|non}"),
+            vec!["saucisse", "bedge"]
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+// message text prepared once for every reaction matcher
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Text {
+    // without discord markup, original case and accents
+    pub stripped: String,
+    // without markup, lowercase and without diacritics
+    pub normalized: String,
+    pub tokens: Vec<String>,
+}
+
+impl Text {
+    pub fn new(content: &str) -> Self {
+        let stripped = strip_markup(content);
+        let normalized = normalize(&stripped);
+        let tokens = split_words(&normalized);
+        Self {
+            stripped,
+            normalized,
+            tokens,
+        }
+    }
+
+    // true if one of the targets appears as whole words
+    pub fn has_word(&self, targets: &[&str]) -> bool {
+        targets.iter().any(|target| {
+            let target_tokens = tokenize(target);
+            !target_tokens.is_empty()
+                && self
+                    .tokens
+                    .windows(target_tokens.len())
+                    .any(|window| window == target_tokens.as_slice())
+        })
+    }
+
+    // true if one of the targets appears anywhere, even inside a word
+    pub fn has_substring(&self, targets: &[&str]) -> bool {
+        targets
+            .iter()
+            .map(|target| normalize(target))
+            .any(|target| !target.is_empty() && self.normalized.contains(&target))
+    }
+
+    // true if the last word ends with one of the targets
+    pub fn ends_with(&self, targets: &[&str]) -> bool {
+        let Some(last) = self.tokens.last() else {
+            return false;
+        };
+        targets
+            .iter()
+            .filter_map(|target| tokenize(target).pop())
+            .any(|target| last.ends_with(&target))
+    }
+}
+
+pub fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).unwrap_or_else(|e| panic!("bad regex {pattern} : {e}")))
+}
+
+// removes code blocks, quotes, mentions and custom emojis
+pub fn strip_markup(content: &str) -> String {
+    static CODE_BLOCK: OnceLock<Regex> = OnceLock::new();
+    static INLINE_CODE: OnceLock<Regex> = OnceLock::new();
+    static MENTION: OnceLock<Regex> = OnceLock::new();
+
+    let without_blocks = regex(&CODE_BLOCK, r"(?s)```.*?```").replace_all(content, " ");
+    let without_code = regex(&INLINE_CODE, r"`[^`]*`").replace_all(&without_blocks, " ");
+
+    let mut lines = Vec::new();
+    for line in without_code.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with(">>> ") {
+            // everything after a block quote is quoted
+            break;
+        }
+        if !trimmed.starts_with("> ") {
+            lines.push(line);
+        }
+    }
+
+    regex(&MENTION, r"<(?:@[!&]?|#)\d+>|<a?:\w+:\d+>")
+        .replace_all(&lines.join("\n"), " ")
+        .into_owned()
+}
+
+// lowercase and without diacritics, "Société" and "societe" become the same
+pub fn normalize(s: &str) -> String {
+    s.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            'œ' => "oe".to_owned(),
+            'æ' => "ae".to_owned(),
+            _ => c.to_string(),
+        })
+        .collect()
+}
+
+fn split_words(normalized: &str) -> Vec<String> {
+    normalized
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+pub fn tokenize(s: &str) -> Vec<String> {
+    split_words(&normalize(&strip_markup(s)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(tokenize("quoi?"), vec!["quoi"]);
+        assert_eq!(tokenize("C'est sus!"), vec!["c", "est", "sus"]);
+        assert_eq!(tokenize("Société, SAUCISSE."), vec!["societe", "saucisse"]);
+        assert_eq!(tokenize("cœur"), vec!["coeur"]);
+        assert_eq!(tokenize("<@123456> salut <:afungus:987654>"), vec!["salut"]);
+        assert_eq!(
+            tokenize("avant `sus` ```\nsus\n``` après"),
+            vec!["avant", "apres"]
+        );
+        assert_eq!(tokenize("> sus\nnon"), vec!["non"]);
+        assert_eq!(tokenize("oui\n>>> sus\nsus"), vec!["oui"]);
+    }
+
+    #[test]
+    fn test_matchers() {
+        let text = Text::new("Bonjour la Société, tu fais quoi ?");
+        assert!(text.has_word(&["societe"]));
+        assert!(text.has_word(&["société"]));
+        assert!(text.has_word(&["la société"]));
+        assert!(!text.has_word(&["soc"]));
+        assert!(text.has_substring(&["soc"]));
+        assert!(text.ends_with(&["quoi"]));
+        assert!(Text::new("pourquoi").ends_with(&["quoi ?"]));
+        assert!(!Text::new("quoi de neuf").ends_with(&["quoi"]));
+        assert!(!Text::new("`quoi`").ends_with(&["quoi"]));
+    }
+}
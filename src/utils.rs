@@ -53,6 +53,14 @@ pub async fn get_user_name<'a>(
     user.name.clone()
 }
 
+pub async fn is_admin(ctx: crate::commands::Context<'_>) -> bool {
+    ctx.author_member().await.map_or(false, |member| {
+        member
+            .permissions
+            .map_or(false, |perm| perm.administrator())
+    })
+}
+
 // TODO: need a message to research from as a parameter ?
 // pub async fn find_message(
 //     ctx: &Context,